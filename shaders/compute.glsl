@@ -1,6 +1,9 @@
 #version 460
 
-layout(local_size_x = 1, local_size_y = 1, local_size_z = 1) in;
+// Must match `TILE_SIZE` in simulator.rs
+#define TILE_SIZE 16
+
+layout(local_size_x = TILE_SIZE, local_size_y = TILE_SIZE, local_size_z = 1) in;
 
 layout(push_constant) uniform PushConstantData {
     float time_step;
@@ -10,17 +13,37 @@ layout(push_constant) uniform PushConstantData {
     int brush_y;
     int brush_enabled;
     float brush_radius;
-    int brush_value;
+    float brush_value;
     int brush_layer;
+    // Must match `BrushMode` in ui_state.rs
+    int brush_mode;
     int boundary_condition;
+    float absorbing_width;
+    float absorbing_strength;
+    float cell_spacing;
+    float hbar;
+    float mass;
     int stage;
+    // The simulation time halfway through the step, at which the driven potentials are evaluated
+    float time;
+    int drive_count;
 } pc;
 
 layout(set = 0, binding = 0, rgba32f) uniform image2D img;
 
+layout(set = 0, binding = 1) readonly buffer Drives {
+    vec4 drives[];
+};
+
+#include "drives.glsl"
+
+// The channel each stage differentiates, for the workgroup's cells plus a one cell border
+shared float tile[TILE_SIZE + 2][TILE_SIZE + 2];
+
 float U(int x, int y) {
     switch (pc.boundary_condition) {
         case 0: // Dirichlet
+        case 3: // Absorbing
         {
             if (x < 0 || x >= imageSize(img).x || y < 0 || y >= imageSize(img).y)
                 return 0.0;
@@ -43,13 +66,10 @@ float U(int x, int y) {
     return imageLoad(img, ivec2(x, y)).r;
 }
 
-float U(ivec2 location) {
-    return U(location.x, location.y);
-}
-
 float V(int x, int y) {
     switch (pc.boundary_condition) {
         case 0: // Dirichlet
+        case 3: // Absorbing
         {
             if (x < 0 || x >= imageSize(img).x || y < 0 || y >= imageSize(img).y)
                 return 0.0;
@@ -72,10 +92,6 @@ float V(int x, int y) {
     return imageLoad(img, ivec2(x, y)).g;
 }
 
-float V(ivec2 location) {
-    return V(location.x, location.y);
-}
-
 float potential(int x, int y) {
     if (x < 0 || x >= imageSize(img).x || y < 0 || y >= imageSize(img).y)
         return 0.0;
@@ -86,58 +102,87 @@ float potential(ivec2 location) {
     return potential(location.x, location.y);
 }
 
-float du_dt(int x, int y) {
-    float dx = 1.0;
+// The complex absorbing potential, which ramps up quadratically over the outermost cells
+float absorption(ivec2 location) {
+    if (pc.boundary_condition != 3 || pc.absorbing_width <= 0.0)
+        return 0.0;
 
-    float v = V(x, y);
-    float dv_dx_0 = (v - V(x - 1, y)) / dx;
-    float dv_dx_1 = (V(x + 1, y) - v) / dx;
+    ivec2 size = imageSize(img);
+    int edge_distance = min(min(location.x, location.y), min(size.x - 1 - location.x, size.y - 1 - location.y));
+    float depth = max(0.0, 1.0 - float(edge_distance) / pc.absorbing_width);
+    return pc.absorbing_strength * depth * depth;
+}
 
-    float dv_dy_0 = (v - V(x, y - 1)) / dx;
-    float dv_dy_1 = (V(x, y + 1) - v) / dx;
+// Reads the channel the current stage differentiates, U in stage 0 and V in stage 1. Cells that
+// stay outside of the grid after applying the boundary condition read as 0, as do the neighbours
+// of the cells that hang over the edges, which only wrap by one cell.
+float stage_channel(int x, int y) {
+    ivec2 size = imageSize(img);
+    if (x < -1 || x > size.x || y < -1 || y > size.y)
+        return 0.0;
+    if (pc.boundary_condition != 2 && (x < 0 || x >= size.x || y < 0 || y >= size.y))
+        return 0.0;
+    return pc.stage == 0 ? U(x, y) : V(x, y);
+}
 
-    if (pc.boundary_condition == 1) { // Neumann
-        if (x == 0) dv_dx_0 = 0.0;
-        if (x == imageSize(img).x - 1) dv_dx_1 = 0.0;
-        if (y == 0) dv_dy_0 = 0.0;
-        if (y == imageSize(img).y - 1) dv_dy_1 = 0.0;
-    }
-    
-    float d2v_dx2 = (dv_dx_1 - dv_dx_0) / dx;
-    float d2v_dy2 = (dv_dy_1 - dv_dy_0) / dx;
+// Each cell loads itself into the tile, and the cells along the edges of the workgroup also load
+// their neighbours outside of it. The corners are not needed by the 5-point stencil.
+void load_tile(ivec2 location) {
+    ivec2 local_id = ivec2(gl_LocalInvocationID.xy);
+    ivec2 t = local_id + 1;
+    int x = location.x;
+    int y = location.y;
 
-    return -0.5 * (d2v_dx2 + d2v_dy2) + potential(x, y) * V(x, y);
+    tile[t.y][t.x] = stage_channel(x, y);
+    if (local_id.x == 0) tile[t.y][0] = stage_channel(x - 1, y);
+    if (local_id.x == TILE_SIZE - 1) tile[t.y][TILE_SIZE + 1] = stage_channel(x + 1, y);
+    if (local_id.y == 0) tile[0][t.x] = stage_channel(x, y - 1);
+    if (local_id.y == TILE_SIZE - 1) tile[TILE_SIZE + 1][t.x] = stage_channel(x, y + 1);
 }
 
-float du_dt(ivec2 location) {
-    return du_dt(location.x, location.y);
-}
+// The 5-point Laplacian of the tiled channel at `location`
+float laplacian(ivec2 location) {
+    ivec2 t = ivec2(gl_LocalInvocationID.xy) + 1;
+    float dx = pc.cell_spacing;
 
-float dv_dt(int x, int y) {
-    float dx = 1.0;
+    float c = tile[t.y][t.x];
+    float dc_dx_0 = (c - tile[t.y][t.x - 1]) / dx;
+    float dc_dx_1 = (tile[t.y][t.x + 1] - c) / dx;
 
-    float u = U(x, y);
-    float du_dx_0 = (u - U(x - 1, y)) / dx;
-    float du_dx_1 = (U(x + 1, y) - u) / dx;
+    float dc_dy_0 = (c - tile[t.y - 1][t.x]) / dx;
+    float dc_dy_1 = (tile[t.y + 1][t.x] - c) / dx;
 
-    float du_dy_0 = (u - U(x, y - 1)) / dx;
-    float du_dy_1 = (U(x, y + 1) - u) / dx;
-    
     if (pc.boundary_condition == 1) { // Neumann
-        if (x == 0) du_dx_0 = 0.0;
-        if (x == imageSize(img).x - 1) du_dx_1 = 0.0;
-        if (y == 0) du_dy_0 = 0.0;
-        if (y == imageSize(img).y - 1) du_dy_1 = 0.0;
+        if (location.x == 0) dc_dx_0 = 0.0;
+        if (location.x == imageSize(img).x - 1) dc_dx_1 = 0.0;
+        if (location.y == 0) dc_dy_0 = 0.0;
+        if (location.y == imageSize(img).y - 1) dc_dy_1 = 0.0;
     }
 
-    float d2u_dx2 = (du_dx_1 - du_dx_0) / dx;
-    float d2u_dy2 = (du_dy_1 - du_dy_0) / dx;
+    float d2c_dx2 = (dc_dx_1 - dc_dx_0) / dx;
+    float d2c_dy2 = (dc_dy_1 - dc_dy_0) / dx;
+
+    return d2c_dx2 + d2c_dy2;
+}
+
+// The static potential in the grid plus the driven potentials at this step
+float total_potential(ivec2 location) {
+    return potential(location) + driven_potential(vec2(location), pc.time, pc.drive_count);
+}
+
+// ħ/2m, the factor in front of the Laplacian
+float kinetic_coefficient() {
+    return pc.hbar / (2.0 * pc.mass);
+}
 
-    return 0.5 * (d2u_dx2 + d2u_dy2) - potential(x, y) * V(x, y);
+// Only valid in stage 1, when the tile holds V
+float du_dt(ivec2 location, float v) {
+    return -kinetic_coefficient() * laplacian(location) + total_potential(location) / pc.hbar * v;
 }
 
-float dv_dt(ivec2 location) {
-    return dv_dt(location.x, location.y);
+// Only valid in stage 0, when the tile holds U
+float dv_dt(ivec2 location, float u) {
+    return kinetic_coefficient() * laplacian(location) - total_potential(location) / pc.hbar * u;
 }
 
 void main() {
@@ -145,11 +190,26 @@ void main() {
     int x = location.x;
     int y = location.y;
 
+    // The last workgroups in each direction can hang over the edges of the grid. Their extra
+    // cells still have to help fill the tile, so they only stop after the barrier.
+    bool inside = x < imageSize(img).x && y < imageSize(img).y;
+
+    // The brush setting is the same for the whole dispatch, so the barrier is still reached by
+    // every invocation or none of them
+    if (pc.brush_enabled != 1) {
+        load_tile(location);
+        memoryBarrierShared();
+        barrier();
+    }
+
+    if (!inside)
+        return;
+
     float dt = pc.time_step;
 
     ivec2 brush_pos = ivec2(pc.brush_x, pc.brush_y);
     float brush_radius = pc.brush_radius;
-    float brush_value = float(pc.brush_value);
+    float brush_value = pc.brush_value;
     float r = distance(vec2(location), vec2(brush_pos));
 
     vec4 grid_cell = imageLoad(img, location);
@@ -160,39 +220,73 @@ void main() {
 
     float pi = 3.14159265;
     float s = pc.brush_radius;
-    float m = 1.0;
-    float v_x0 = pc.speed * -cos(pc.theta);
-    float v_y0 = pc.speed * sin(pc.theta);
     float r_x = float(brush_pos.x - x) / s;
     float r_y = float(brush_pos.y - y) / s;
 
+    // The packet moves at `speed`, so its wave number is k = m·v/ħ along the direction of motion
+    // and the phase advances by k·dx per cell in that direction, with y pointing up
+    float k = pc.mass * pc.speed / pc.hbar;
+    vec2 offset = vec2(x - brush_pos.x, brush_pos.y - y);
+    float phase = k * pc.cell_spacing * (cos(pc.theta) * offset.x + sin(pc.theta) * offset.y);
+
     if (pc.brush_enabled == 1) {
         if (pc.brush_layer == 4) {
-            float u_new = brush_value * (exp(-1 / (4 * s * s) * (r_x * r_x + r_y * r_y))) / sqrt(2 * pi * s * s) * cos(m * (v_x0 * r_x + v_y0 * r_y));
-            float v_new = brush_value * (exp(-1 / (4 * s * s) * (r_x * r_x + r_y * r_y))) / sqrt(2 * pi * s * s) * sin(m * (v_x0 * r_x + v_y0 * r_y));
-
-            if (abs(u_new) < abs(u)) u_new = u;
-            if (abs(v_new) < abs(v)) v_new = v;
+            float envelope = exp(-1 / (4 * s * s) * (r_x * r_x + r_y * r_y));
+            float u_new = brush_value * envelope / sqrt(2 * pi * s * s) * cos(phase);
+            float v_new = brush_value * envelope / sqrt(2 * pi * s * s) * sin(phase);
 
-            imageStore(img, location, vec4(u_new, v_new, potential, old_v));
+            switch (pc.brush_mode) {
+                case 0: // Set
+                    if (abs(u_new) < abs(u)) u_new = u;
+                    if (abs(v_new) < abs(v)) v_new = v;
+                    imageStore(img, location, vec4(u_new, v_new, potential, old_v));
+                    break;
+                case 1: // Add
+                    imageStore(img, location, vec4(u + u_new, v + v_new, potential, old_v + v_new));
+                    break;
+                case 2: // Subtract
+                    imageStore(img, location, vec4(u - u_new, v - v_new, potential, old_v - v_new));
+                    break;
+                case 3: // Erase, fading the wave function out with the packet's envelope
+                {
+                    float keep = 1.0 - envelope;
+                    imageStore(img, location, vec4(u * keep, v * keep, potential, old_v * keep));
+                }
+                break;
+            }
         } else if (pc.brush_layer == 3 && r < brush_radius) {
-            potential = max(potential, brush_value * exp(-pow(r, 2) / brush_radius));
+            // The weight of the brush at this cell, 1 at the centre
+            float weight = exp(-pow(r, 2) / brush_radius);
+            switch (pc.brush_mode) {
+                case 0: // Set
+                    potential = mix(potential, brush_value, weight);
+                    break;
+                case 1: // Add
+                    potential += brush_value * weight;
+                    break;
+                case 2: // Subtract
+                    potential -= brush_value * weight;
+                    break;
+                case 3: // Erase
+                    potential *= 1.0 - weight;
+                    break;
+            }
             imageStore(img, location, vec4(u, v, potential, old_v));
         }
     } else {
         switch (pc.stage) {
             case 0:
             {
-                float dv_dt = dv_dt(location);
-                float new_v = v + dv_dt * dt;
-                imageStore(img, location, vec4(U(location), new_v, potential, v));
+                float dv_dt = dv_dt(location, u);
+                float new_v = (v + dv_dt * dt) * exp(-absorption(location) * dt);
+                imageStore(img, location, vec4(u, new_v, potential, v));
             }
             break;
             case 1:
             {
-                float du_dt = du_dt(location);
-                float new_u = u + du_dt * dt;
-                imageStore(img, location, vec4(new_u, V(location), potential, old_v));
+                float du_dt = du_dt(location, v);
+                float new_u = (u + du_dt * dt) * exp(-absorption(location) * dt);
+                imageStore(img, location, vec4(new_u, v, potential, old_v));
             }
             break;
         }
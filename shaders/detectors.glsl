@@ -0,0 +1,128 @@
+#version 460
+
+// Must match `TILE_SIZE` in simulator.rs
+#define TILE_SIZE 16
+#define GROUP_SIZE (TILE_SIZE * TILE_SIZE)
+
+// Must match `MAX_DETECTORS`, `MAX_VERTICES` and `DETECTOR_STRIDE` in detector.rs
+#define MAX_DETECTORS 8
+#define MAX_VERTICES 16
+#define DETECTOR_STRIDE (1 + MAX_VERTICES / 2)
+
+layout(local_size_x = TILE_SIZE, local_size_y = TILE_SIZE, local_size_z = 1) in;
+
+layout(push_constant) uniform DetectorPushConstants {
+    int boundary_condition;
+    int detector_count;
+    // Which of the measured steps in the buffer the readings belong to, or -1 if the step is only
+    // added to the outflow
+    int slot;
+    // The number of measured steps, after which each workgroup keeps its running outflow
+    int measured;
+    float time_step;
+    // ħ/(m·dx²), which turns Im(Ψ*·Ψₙ) into the flux between two cells
+    float coupling;
+} pc;
+
+layout(set = 0, binding = 0, rgba32f) uniform readonly image2D img;
+
+layout(set = 0, binding = 1) readonly buffer Detectors {
+    vec4 detectors[];
+};
+
+// For every measured step, workgroup and detector the partial sums of the probability, the flux
+// and the outflow so far, followed by the running outflow of every workgroup and detector. The
+// buffer starts out zeroed.
+layout(set = 0, binding = 2) buffer Sums {
+    vec4 sums[];
+};
+
+shared vec2 partial[GROUP_SIZE][MAX_DETECTORS];
+
+// Whether `p` is inside the polygon of detector `d`, by the even-odd rule like
+// `Detector::contains`
+bool inside(int d, vec2 p) {
+    int base = d * DETECTOR_STRIDE;
+    int count = int(detectors[base].x);
+    bool result = false;
+    for (int i = 0; i < count; i++) {
+        int j = (i + 1) % count;
+        vec4 pair_a = detectors[base + 1 + i / 2];
+        vec4 pair_b = detectors[base + 1 + j / 2];
+        vec2 a = i % 2 == 0 ? pair_a.xy : pair_a.zw;
+        vec2 b = j % 2 == 0 ? pair_b.xy : pair_b.zw;
+        if ((a.y > p.y) != (b.y > p.y) && p.x < a.x + (p.y - a.y) / (b.y - a.y) * (b.x - a.x))
+            result = !result;
+    }
+    return result;
+}
+
+void main() {
+    ivec2 location = ivec2(gl_GlobalInvocationID.xy);
+    ivec2 size = imageSize(img);
+    uint i = gl_LocalInvocationIndex;
+
+    for (int d = 0; d < MAX_DETECTORS; d++)
+        partial[i][d] = vec2(0.0);
+
+    // The cells hanging over the edges of the grid add nothing, but still take part in the
+    // reduction
+    if (location.x < size.x && location.y < size.y) {
+        vec4 cell = imageLoad(img, location);
+        float probability = cell.r * cell.r + cell.g * cell.a;
+
+        ivec2 neighbours[4] = {
+            location - ivec2(1, 0),
+            location + ivec2(1, 0),
+            location - ivec2(0, 1),
+            location + ivec2(0, 1),
+        };
+
+        for (int d = 0; d < pc.detector_count; d++) {
+            if (!inside(d, vec2(location)))
+                continue;
+
+            float flux = 0.0;
+            for (int n = 0; n < 4; n++) {
+                ivec2 neighbour = neighbours[n];
+                // Only periodic edges carry any flux out of the grid, see `detector::measure`
+                if (pc.boundary_condition == 2)
+                    neighbour = (neighbour + size) % size;
+                else if (any(lessThan(neighbour, ivec2(0))) || any(greaterThanEqual(neighbour, size)))
+                    continue;
+                if (inside(d, vec2(neighbour)))
+                    continue;
+
+                vec2 other = imageLoad(img, neighbour).rg;
+                flux += pc.coupling * (cell.r * other.y - cell.g * other.x);
+            }
+            partial[i][d] = vec2(probability, flux);
+        }
+    }
+    memoryBarrierShared();
+    barrier();
+
+    for (uint stride = GROUP_SIZE / 2; stride > 0; stride /= 2) {
+        if (i < stride) {
+            for (int d = 0; d < MAX_DETECTORS; d++)
+                partial[i][d] += partial[i + stride][d];
+        }
+        memoryBarrierShared();
+        barrier();
+    }
+
+    if (i == 0) {
+        uint group = gl_WorkGroupID.y * gl_NumWorkGroups.x + gl_WorkGroupID.x;
+        uint groups = gl_NumWorkGroups.x * gl_NumWorkGroups.y;
+        for (int d = 0; d < pc.detector_count; d++) {
+            // Only this workgroup touches its running outflow, and the dispatches of the steps
+            // follow each other, so it can be kept in the buffer
+            uint running = (uint(pc.measured) * groups + group) * MAX_DETECTORS + d;
+            float outflow = sums[running].x + partial[0][d].y * pc.time_step;
+            sums[running].x = outflow;
+
+            if (pc.slot >= 0)
+                sums[(uint(pc.slot) * groups + group) * MAX_DETECTORS + d] = vec4(partial[0][d], outflow, 0.0);
+        }
+    }
+}
@@ -0,0 +1,141 @@
+// Time-dependent potentials and expressions, packed by `DrivenPotential::pack` in drive.rs. The
+// including shader declares the buffer that holds them as `drives`.
+
+// Must match `DRIVE_STRIDE` and `EXPRESSION_KIND` in drive.rs
+#define DRIVE_STRIDE 5
+#define EXPRESSION_KIND 5
+
+// Must match `STACK_SIZE` in expression.rs
+#define EXPRESSION_STACK_SIZE 16
+
+// The potential of the shape in `drives[base]` and `drives[base + 1]` at `p`, like
+// `PotentialShape::value`
+float drive_shape(int base, vec2 p) {
+    vec4 a = drives[base];
+    vec4 b = drives[base + 1];
+
+    switch (int(a.x)) {
+        case 0: // Rectangle
+            return p.x >= a.y && p.x < a.y + a.w && p.y >= a.z && p.y < a.z + b.x ? b.y : 0.0;
+        case 1: // Circle
+            return distance(p, a.yz) <= a.w ? b.x : 0.0;
+        case 2: // Slits
+        {
+            if (p.x < a.y || p.x >= a.y + a.z)
+                return 0.0;
+
+            int count = int(b.x);
+            float first = b.w - b.z * float(count - 1) / 2.0;
+            for (int i = 0; i < count; i++) {
+                if (abs(p.y - (first + b.z * float(i))) < b.y / 2.0)
+                    return 0.0;
+            }
+            return a.w;
+        }
+        case 3: // Harmonic
+            return 0.5 * a.w * dot(p - a.yz, p - a.yz);
+        case 4: // Field
+            return -dot(vec2(a.w, b.x), p - a.yz);
+    }
+    return 0.0;
+}
+
+// The power a^b, which GLSL leaves undefined for negative a even when b is a whole number
+float expression_power(float a, float b) {
+    if (a >= 0.0 || b != round(b))
+        return pow(a, b);
+    float magnitude = pow(-a, b);
+    return mod(b, 2.0) == 0.0 ? magnitude : -magnitude;
+}
+
+// Runs the stack machine program of the expression packed at `drives[base]`, with the opcodes of
+// `Variable`, `Unary` and `Binary` in expression.rs
+float expression_potential(int base, vec2 p, float t) {
+    int instruction_count = int(drives[base].y);
+    vec4 frame = drives[base + 1];
+    vec2 q = (p - frame.xy) * frame.z;
+
+    float stack[EXPRESSION_STACK_SIZE];
+    int top = 0;
+    for (int i = 0; i < instruction_count; i++) {
+        vec4 instruction = drives[base + 2 + i];
+        int op = int(instruction.x);
+        if (op == 0) {
+            stack[top++] = instruction.y;
+        } else if (op < 10) {
+            float values[4] = { q.x, q.y, length(q), t };
+            stack[top++] = values[op - 1];
+        } else if (op < 30) {
+            float a = stack[top - 1];
+            switch (op) {
+                case 10: a = -a; break;
+                case 11: a = a == 0.0 ? 1.0 : 0.0; break;
+                case 12: a = abs(a); break;
+                case 13: a = sqrt(a); break;
+                case 14: a = exp(a); break;
+                case 15: a = log(a); break;
+                case 16: a = sin(a); break;
+                case 17: a = cos(a); break;
+                case 18: a = tan(a); break;
+                case 19: a = tanh(a); break;
+                case 20: a = floor(a); break;
+                case 21: a = ceil(a); break;
+                case 22: a = sign(a); break;
+            }
+            stack[top - 1] = a;
+        } else {
+            float b = stack[--top];
+            float a = stack[top - 1];
+            switch (op) {
+                case 30: a = a + b; break;
+                case 31: a = a - b; break;
+                case 32: a = a * b; break;
+                case 33: a = a / b; break;
+                case 34: a = expression_power(a, b); break;
+                case 35: a = float(a < b); break;
+                case 36: a = float(a <= b); break;
+                case 37: a = float(a > b); break;
+                case 38: a = float(a >= b); break;
+                case 39: a = float(a == b); break;
+                case 40: a = float(a != b); break;
+                case 41: a = float(a != 0.0 && b != 0.0); break;
+                case 42: a = float(a != 0.0 || b != 0.0); break;
+                case 43: a = min(a, b); break;
+                case 44: a = max(a, b); break;
+                case 45: a = atan(a, b); break;
+            }
+            stack[top - 1] = a;
+        }
+    }
+    return top > 0 ? stack[0] : 0.0;
+}
+
+// The sum of the first `count` driven potentials at cell position `p` and time `t`, like
+// `DrivenPotential::value`
+float driven_potential(vec2 p, float t, int count) {
+    float total = 0.0;
+    int base = 0;
+    for (int i = 0; i < count; i++) {
+        if (int(drives[base].x) == EXPRESSION_KIND) {
+            total += expression_potential(base, p, t);
+            base += 2 + int(drives[base].y);
+            continue;
+        }
+
+        vec4 modulation = drives[base + 2];
+        vec4 pulse_velocity = drives[base + 3];
+        vec4 swing = drives[base + 4];
+
+        float wave = sin(modulation.z * t + modulation.w);
+        float strength = modulation.x + modulation.y * wave;
+        if (pulse_velocity.y > 0.0) {
+            float s = (t - pulse_velocity.x) / pulse_velocity.y;
+            strength *= exp(-0.5 * s * s);
+        }
+        vec2 offset = pulse_velocity.zw * t + swing.xy * wave;
+
+        total += strength * drive_shape(base, p - offset);
+        base += DRIVE_STRIDE;
+    }
+    return total;
+}
@@ -6,10 +6,31 @@ layout(location = 0) in vec2 out_uv;
 layout(set = 0, binding = 0) uniform sampler s;
 layout(set = 0, binding = 1) uniform texture2D tex;
 
+layout(set = 0, binding = 2) readonly buffer Drives {
+    vec4 drives[];
+};
+
+// The momentum-space wave function, with k = 0 in the centre, filled in for the Momentum layer
+layout(set = 0, binding = 3) uniform texture2D momentum_tex;
+
 layout(push_constant) uniform PushConstantData {
     int visible_layer;
+    // The simulation time, at which the driven potentials are shown
+    float time;
+    int drive_count;
+    // ħ/m and the cell spacing, for the probability current
+    float hbar_over_mass;
+    float cell_spacing;
+    // The brightness of the Current layer and the length of the arrows per unit of |j|
+    float current_scale;
+    int current_arrows;
 } pc;
 
+// The number of cells between the centres of neighbouring current arrows
+#define ARROW_SPACING 8.0
+
+#include "drives.glsl"
+
 // taken from https://www.shadertoy.com/view/WlfXRN
 vec3 plasma(float t) {
     const vec3 c0 = vec3(0.05873234392399702, 0.02333670892565664, 0.5433401826748754);
@@ -49,10 +70,63 @@ vec3 rgb2hsv(vec3 c)
     return vec3(abs(q.z + (q.w - q.y) / (6.0 * d + e)), d / (q.x + e), q.x);
 }
 
+// The probability current j = (ħ/m)·Im(Ψ*∇Ψ) = (ħ/m)·(Re Ψ·∇Im Ψ - Im Ψ·∇Re Ψ) at `cell`, with
+// central differences that fall back to one-sided ones along the edges
+vec2 probability_current(ivec2 cell) {
+    ivec2 last = textureSize(sampler2D(tex, s), 0) - 1;
+    cell = clamp(cell, ivec2(0), last);
+    ivec2 left = clamp(cell - ivec2(1, 0), ivec2(0), last);
+    ivec2 right = clamp(cell + ivec2(1, 0), ivec2(0), last);
+    ivec2 up = clamp(cell - ivec2(0, 1), ivec2(0), last);
+    ivec2 down = clamp(cell + ivec2(0, 1), ivec2(0), last);
+
+    vec2 psi = texelFetch(sampler2D(tex, s), cell, 0).rg;
+    vec2 d_dx = (texelFetch(sampler2D(tex, s), right, 0).rg - texelFetch(sampler2D(tex, s), left, 0).rg)
+            / (float(right.x - left.x) * pc.cell_spacing);
+    vec2 d_dy = (texelFetch(sampler2D(tex, s), down, 0).rg - texelFetch(sampler2D(tex, s), up, 0).rg)
+            / (float(down.y - up.y) * pc.cell_spacing);
+
+    return pc.hbar_over_mass * vec2(psi.x * d_dx.y - psi.y * d_dx.x, psi.x * d_dy.y - psi.y * d_dy.x);
+}
+
+// The distance from `p` to the line segment from `a` to `b`
+float segment_distance(vec2 p, vec2 a, vec2 b) {
+    vec2 ab = b - a;
+    float along = clamp(dot(p - a, ab) / max(dot(ab, ab), 1e-6), 0.0, 1.0);
+    return distance(p, a + along * ab);
+}
+
+// How much of the current arrow in the block of cells around `position` covers it, from 0 to 1.
+// Each block of ARROW_SPACING cells shows the current at its centre, with arrows that grow with
+// |j| until they fill the block.
+float current_arrow(vec2 position) {
+    vec2 center = (floor(position / ARROW_SPACING) + 0.5) * ARROW_SPACING;
+    vec2 j = probability_current(ivec2(center));
+
+    float half_length = min(length(j) * pc.current_scale, 1.0) * 0.45 * ARROW_SPACING;
+    if (half_length < 0.5)
+        return 0.0;
+
+    vec2 direction = normalize(j);
+    vec2 normal = vec2(-direction.y, direction.x);
+    vec2 tip = center + direction * half_length;
+    vec2 head_base = tip - 0.6 * half_length * direction;
+    vec2 barb = 0.3 * half_length * normal;
+
+    float d = segment_distance(position, center - direction * half_length, tip);
+    d = min(d, segment_distance(position, tip, head_base + barb));
+    d = min(d, segment_distance(position, tip, head_base - barb));
+    return 1.0 - smoothstep(0.25, 0.6, d);
+}
+
 void main() {
     vec4 color = texture(sampler2D(tex, s), out_uv);
     int layer = pc.visible_layer;
 
+    // The position in cells, with the centre of the top left cell at 0
+    vec2 position = out_uv * vec2(textureSize(sampler2D(tex, s), 0)) - 0.5;
+    color.b += driven_potential(position, pc.time, pc.drive_count);
+
     float layers[4] = {
             color.r, // Real
             color.g, // Imaginary
@@ -61,11 +135,25 @@ void main() {
         };
 
     switch (layer) {
-        case 4: {
-            f_color = vec4(hsv2rgb(vec3(atan(color.g, color.r), 1.0, layers[2])) + min(1.0, layers[3]) * plasma(layers[3]), 1.0);
+        case 4: { // Wave Function, with the hue going once around the colour wheel per 2π of phase
+            float phase = atan(color.g, color.r) / (2.0 * 3.14159265);
+            f_color = vec4(hsv2rgb(vec3(phase, 1.0, layers[2])) + min(1.0, layers[3]) * plasma(layers[3]), 1.0);
         } break;
+        case 5: { // Current, with the hue showing its direction counterclockwise from +x
+            vec2 j = probability_current(ivec2(round(position)));
+            float angle = atan(-j.y, j.x) / (2.0 * 3.14159265);
+            f_color = vec4(hsv2rgb(vec3(angle, 1.0, min(1.0, length(j) * pc.current_scale))) + min(1.0, layers[3]) * plasma(layers[3]), 1.0);
+        } break;
+        case 6: { // Momentum, |φ(k)|² without the potential, which has no meaning in momentum space
+            vec4 phi = texture(sampler2D(momentum_tex, s), out_uv);
+            f_color = vec4(turbo(phi.r * phi.r + phi.g * phi.a), 1.0);
+            return;
+        }
         default: {
             f_color = vec4(turbo(layers[layer]) + min(1.0, layers[3]) * plasma(layers[3]), 1.0);
         } break;
     }
+
+    if (pc.current_arrows == 1)
+        f_color.rgb = mix(f_color.rgb, vec3(1.0), current_arrow(position));
 }
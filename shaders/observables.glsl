@@ -0,0 +1,114 @@
+#version 460
+
+// Must match `TILE_SIZE` in simulator.rs
+#define TILE_SIZE 16
+#define GROUP_SIZE (TILE_SIZE * TILE_SIZE)
+
+layout(local_size_x = TILE_SIZE, local_size_y = TILE_SIZE, local_size_z = 1) in;
+
+layout(push_constant) uniform ObservablesPushConstants {
+    int boundary_condition;
+    // Which of the measured steps in the buffer the sums belong to
+    int slot;
+    // The simulation time of the measured step, at which the driven potentials are evaluated
+    float time;
+    int drive_count;
+} pc;
+
+layout(set = 0, binding = 0, rgba32f) uniform readonly image2D img;
+
+// Two vec4s of partial sums for every workgroup of every measured step, see `Observables` in
+// observables.rs for what they hold
+layout(set = 0, binding = 1) buffer Sums {
+    vec4 sums[];
+};
+
+layout(set = 0, binding = 2) readonly buffer Drives {
+    vec4 drives[];
+};
+
+#include "drives.glsl"
+
+shared vec4 partial_a[GROUP_SIZE];
+shared vec4 partial_b[GROUP_SIZE];
+
+// Ψ at a neighbouring cell, following the boundary condition the same way the leapfrog kernel
+// does. Neumann edges have no flux through them, which is the same as a neighbour outside that
+// equals the cell at the edge.
+vec2 psi(int x, int y) {
+    ivec2 size = imageSize(img);
+    switch (pc.boundary_condition) {
+        case 1: // Neumann
+            x = clamp(x, 0, size.x - 1);
+            y = clamp(y, 0, size.y - 1);
+            break;
+        case 2: // Periodic
+            x = (x + size.x) % size.x;
+            y = (y + size.y) % size.y;
+            break;
+        default: // Dirichlet and absorbing
+            if (x < 0 || x >= size.x || y < 0 || y >= size.y)
+                return vec2(0.0);
+            break;
+    }
+    return imageLoad(img, ivec2(x, y)).rg;
+}
+
+void main() {
+    ivec2 location = ivec2(gl_GlobalInvocationID.xy);
+    int x = location.x;
+    int y = location.y;
+    uint i = gl_LocalInvocationIndex;
+
+    // The cells hanging over the edges of the grid add nothing, but still take part in the
+    // reduction
+    vec4 a = vec4(0.0);
+    vec4 b = vec4(0.0);
+    if (x < imageSize(img).x && y < imageSize(img).y) {
+        vec4 cell = imageLoad(img, location);
+        float u = cell.r;
+        float v = cell.g;
+        float potential = cell.b + driven_potential(vec2(location), pc.time, pc.drive_count);
+        float old_v = cell.a;
+        float probability = u * u + v * old_v;
+
+        vec2 left = psi(x - 1, y);
+        vec2 right = psi(x + 1, y);
+        vec2 up = psi(x, y - 1);
+        vec2 down = psi(x, y + 1);
+
+        // Im(Ψ*·∇Ψ) from central differences, without the 1/2dx
+        float current_x = u * (right.y - left.y) - v * (right.x - left.x);
+        float current_y = u * (down.y - up.y) - v * (down.x - up.x);
+
+        // |∇Ψ|² from forward differences, without the 1/dx², which sums to the same as -Ψ*·∇²Ψ
+        vec2 dpsi_dx = right - vec2(u, v);
+        vec2 dpsi_dy = down - vec2(u, v);
+        float gradient = dot(dpsi_dx, dpsi_dx) + dot(dpsi_dy, dpsi_dy);
+
+        a = vec4(probability, float(x) * probability, float(y) * probability, current_x);
+        b = vec4(current_y, gradient, potential * probability, 0.0);
+    }
+
+    partial_a[i] = a;
+    partial_b[i] = b;
+    memoryBarrierShared();
+    barrier();
+
+    for (uint stride = GROUP_SIZE / 2; stride > 0; stride /= 2) {
+        if (i < stride) {
+            partial_a[i] += partial_a[i + stride];
+            partial_b[i] += partial_b[i + stride];
+        }
+        memoryBarrierShared();
+        barrier();
+    }
+
+    if (i == 0) {
+        uint group = gl_WorkGroupID.y * gl_NumWorkGroups.x + gl_WorkGroupID.x;
+        uint groups = gl_NumWorkGroups.x * gl_NumWorkGroups.y;
+        uint index = (uint(pc.slot) * groups + group) * 2;
+        sums[index] = partial_a[0];
+        sums[index + 1] = partial_b[0];
+    }
+}
@@ -2,6 +2,7 @@ use vulkano::command_buffer::{
     AutoCommandBufferBuilder, CommandBufferUsage, PrimaryAutoCommandBuffer, allocator::*,
 };
 use vulkano::descriptor_set::allocator::*;
+use vulkano::device::DeviceExtensions;
 use vulkano::memory::allocator::*;
 use vulkano::sync::{self, GpuFuture};
 
@@ -20,7 +21,7 @@ use std::time::Instant;
 
 use crate::quad_renderer::QuadRenderer;
 use crate::simulator::Simulator;
-use crate::ui_state::UIState;
+use crate::ui_state::{BrushMode, SimulationLayer, UIState};
 
 pub struct VulkanManager {
     pub context: VulkanoContext,
@@ -33,7 +34,21 @@ pub struct VulkanManager {
 
 impl VulkanManager {
     pub fn new() -> Self {
-        let context = VulkanoContext::new(VulkanoConfig::default());
+        Self::with_config(VulkanoConfig::default())
+    }
+
+    /// Creates a manager for running simulations without a window, so devices that cannot
+    /// present to a surface are also accepted.
+    pub fn headless() -> Self {
+        Self::with_config(VulkanoConfig {
+            device_extensions: DeviceExtensions::empty(),
+            device_filter_fn: Arc::new(|_| true),
+            ..Default::default()
+        })
+    }
+
+    fn with_config(config: VulkanoConfig) -> Self {
+        let context = VulkanoContext::new(config);
         let windows = VulkanoWindows::default();
 
         let memory_allocator = Arc::new(StandardMemoryAllocator::new_default(
@@ -73,7 +88,16 @@ impl VulkanManager {
         &self,
         builder: AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
     ) {
-        let command_buffer = builder.build().unwrap();
+        self.execute_compute_cmdbuffer(builder.build().unwrap());
+    }
+
+    /// Executes `command_buffer` on the compute queue and waits for it to finish.
+    pub fn execute_compute_cmdbuffer(&self, command_buffer: Arc<PrimaryAutoCommandBuffer>) {
+        // A frame's simulation steps may still be running, and they touch the same grid
+        self.context
+            .compute_queue()
+            .with(|mut queue| queue.wait_idle())
+            .unwrap();
 
         let future = sync::now(self.context.device().clone())
             .then_execute(self.context.compute_queue().clone(), command_buffer)
@@ -104,8 +128,14 @@ impl ApplicationHandler for App {
             |_| {},
         );
 
-        self.simulator = Some(Simulator::new(&self.mgr));
-        self.ui_state = Some(UIState::new(&event_loop, &mut self.mgr));
+        self.ui_state = Some(UIState::new(event_loop, &mut self.mgr));
+        let ui_state = self.ui_state.as_ref().unwrap();
+        self.simulator = Some(Simulator::new(
+            &self.mgr,
+            ui_state.grid_width,
+            ui_state.grid_height,
+        ));
+
         self.renderer = Some(QuadRenderer::new(
             &self.mgr,
             self.simulator.as_ref().unwrap(),
@@ -113,7 +143,7 @@ impl ApplicationHandler for App {
         ));
 
         self.simulator
-            .as_ref()
+            .as_mut()
             .unwrap()
             .compute(&self.mgr, self.ui_state.as_ref().unwrap());
     }
@@ -124,8 +154,6 @@ impl ApplicationHandler for App {
         ui_state.handle_event(&event);
         let quad_renderer = self.renderer.as_mut().unwrap();
 
-        let resolution = 5;
-
         match event {
             WindowEvent::CloseRequested => {
                 event_loop.exit();
@@ -133,23 +161,25 @@ impl ApplicationHandler for App {
             WindowEvent::Resized(_) | WindowEvent::ScaleFactorChanged { .. } => {
                 quad_renderer.window_resized = true;
                 quad_renderer.last_resize_event = Instant::now();
-                let window = self.mgr.windows.get_primary_window().unwrap();
-                let gui_width = ui_state.gui_width;
-                simulator.resize(
-                    &self.mgr,
-                    (window.inner_size().width as f32 - gui_width).max(gui_width) as u32 / resolution,
-                    window.inner_size().height as u32 / resolution,
-                );
             }
             WindowEvent::MouseInput {
                 device_id: _,
                 state,
                 button,
             } => {
-                if state.is_pressed() && button == MouseButton::Left {
+                if button == MouseButton::Left && ui_state.brush_layer == SimulationLayer::Detector {
+                    ui_state.detector_input(state.is_pressed(), &self.mgr, simulator);
+                } else if button == MouseButton::Left && ui_state.brush_layer == SimulationLayer::Screen {
+                    ui_state.screen_input(state.is_pressed(), simulator);
+                } else if button == MouseButton::Left && ui_state.brush_mode == BrushMode::Measure {
+                    if state.is_pressed() {
+                        ui_state.measure(&self.mgr, simulator);
+                    }
+                } else if state.is_pressed() && button == MouseButton::Left {
                     ui_state.brush_enabled = 1;
                 } else if !state.is_pressed() && button == MouseButton::Left {
                     ui_state.brush_enabled = 0;
+                    simulator.end_stroke(&self.mgr);
                 }
             }
             WindowEvent::MouseWheel {
@@ -158,7 +188,7 @@ impl ApplicationHandler for App {
                 phase: _,
             } => match delta {
                 MouseScrollDelta::LineDelta(_, y) => {
-                    ui_state.theta += 0.25 * y as f32;
+                    ui_state.theta += 0.25 * y;
                 }
                 MouseScrollDelta::PixelDelta(u) => {
                     ui_state.theta += 0.1 * u.y as f32;
@@ -168,16 +198,44 @@ impl ApplicationHandler for App {
                 device_id: _,
                 position,
             } => {
-                ui_state.brush_x =
-                    ((position.x - ui_state.gui_width as f64) / resolution as f64) as i32;
-                ui_state.brush_y = (position.y / resolution as f64) as i32;
+                // Map the cursor into grid cells through the viewport the grid is drawn in
+                let window = self.mgr.windows.get_primary_window().unwrap();
+                let viewport = QuadRenderer::get_viewport(
+                    window.inner_size().width as f32,
+                    window.inner_size().height as f32,
+                    ui_state.gui_width,
+                    simulator,
+                );
+                ui_state.brush_x = ((position.x as f32 - viewport.offset[0]) / viewport.extent[0]
+                    * simulator.width as f32)
+                    .floor() as i32;
+                ui_state.brush_y = ((position.y as f32 - viewport.offset[1]) / viewport.extent[1]
+                    * simulator.height as f32)
+                    .floor() as i32;
                 ui_state.mouse_x = position.x as f32;
                 ui_state.mouse_y = position.y as f32;
             }
             WindowEvent::RedrawRequested => {
                 ui_state.setup_gui(&self.mgr, simulator);
 
-                simulator.compute(&self.mgr, ui_state);
+                // An eigenstate search replaces the simulation until it has found every state
+                let params = ui_state.step_params();
+                let iterations = ui_state.eigenstate_iterations;
+                if let Some(search) = ui_state
+                    .eigenstate_search
+                    .as_mut()
+                    .filter(|search| !search.is_finished())
+                {
+                    if !ui_state.paused {
+                        simulator.relax(&self.mgr, search, &params, iterations);
+                    }
+                } else if !ui_state.paused || ui_state.brush_enabled == 1 {
+                    // The brush keeps working while paused, but only draws without stepping
+                    simulator.compute(&self.mgr, ui_state);
+                }
+                if ui_state.visible_layer == SimulationLayer::Momentum {
+                    simulator.update_momentum(&self.mgr);
+                }
                 quad_renderer.draw(&mut self.mgr, simulator, ui_state);
             }
             _ => (),
@@ -195,14 +253,14 @@ impl ApplicationHandler for App {
 }
 
 impl App {
-    pub fn new(_event_loop: &EventLoop<()>) -> Arc<Self> {
+    pub fn new(_event_loop: &EventLoop<()>) -> Self {
         let mgr = VulkanManager::new();
 
-        Arc::new(App {
+        App {
             mgr,
             renderer: None,
             simulator: None,
             ui_state: None,
-        })
+        }
     }
 }
@@ -0,0 +1,271 @@
+use crate::grid::Grid;
+use crate::solver::{self, Solver, StepParams};
+use crate::ui_state::BoundaryCondition;
+
+/// A pure Rust port of the leapfrog kernel in `shaders/compute.glsl`.
+///
+/// The arithmetic follows the shader operation for operation so that its output can be checked
+/// against the GPU cell by cell, and so that simulations can run without a Vulkan device.
+pub struct CpuSolver {
+    grid: Grid,
+}
+
+impl CpuSolver {
+    pub fn new(grid: Grid) -> Self {
+        CpuSolver { grid }
+    }
+
+    /// Reads `channel` at `(x, y)`, resolving out of bounds coordinates like `U` and `V` in the
+    /// shader. Out of bounds Neumann reads return 0, but their derivatives are discarded anyway.
+    fn channel(&self, channel: usize, mut x: i32, mut y: i32, bc: BoundaryCondition) -> f32 {
+        let width = self.grid.width as i32;
+        let height = self.grid.height as i32;
+
+        // Absorbing edges read like Dirichlet ones, the damping happens in `absorption`
+        if bc == BoundaryCondition::Periodic {
+            if x < 0 {
+                x = width - 1;
+            }
+            if x >= width {
+                x = 0;
+            }
+            if y < 0 {
+                y = height - 1;
+            }
+            if y >= height {
+                y = 0;
+            }
+        }
+
+        if x < 0 || x >= width || y < 0 || y >= height {
+            return 0.0;
+        }
+
+        self.grid.get(x as u32, y as u32)[channel]
+    }
+
+    fn potential(&self, x: i32, y: i32) -> f32 {
+        self.channel(2, x, y, BoundaryCondition::Dirichlet)
+    }
+
+    /// The 5-point Laplacian of `channel`, with the Neumann edges zeroed like in the shader.
+    fn laplacian(&self, channel: usize, x: i32, y: i32, params: &StepParams) -> f32 {
+        let bc = params.boundary_condition;
+        let dx = params.cell_spacing;
+
+        let c = self.channel(channel, x, y, bc);
+        let mut dc_dx_0 = (c - self.channel(channel, x - 1, y, bc)) / dx;
+        let mut dc_dx_1 = (self.channel(channel, x + 1, y, bc) - c) / dx;
+
+        let mut dc_dy_0 = (c - self.channel(channel, x, y - 1, bc)) / dx;
+        let mut dc_dy_1 = (self.channel(channel, x, y + 1, bc) - c) / dx;
+
+        if bc == BoundaryCondition::Neumann {
+            if x == 0 {
+                dc_dx_0 = 0.0;
+            }
+            if x == self.grid.width as i32 - 1 {
+                dc_dx_1 = 0.0;
+            }
+            if y == 0 {
+                dc_dy_0 = 0.0;
+            }
+            if y == self.grid.height as i32 - 1 {
+                dc_dy_1 = 0.0;
+            }
+        }
+
+        let d2c_dx2 = (dc_dx_1 - dc_dx_0) / dx;
+        let d2c_dy2 = (dc_dy_1 - dc_dy_0) / dx;
+
+        d2c_dx2 + d2c_dy2
+    }
+
+    fn du_dt(&self, x: i32, y: i32, params: &StepParams) -> f32 {
+        let v = self.channel(1, x, y, params.boundary_condition);
+        -params.kinetic_coefficient() * self.laplacian(1, x, y, params)
+            + self.potential(x, y) / params.hbar * v
+    }
+
+    fn dv_dt(&self, x: i32, y: i32, params: &StepParams) -> f32 {
+        let u = self.channel(0, x, y, params.boundary_condition);
+        params.kinetic_coefficient() * self.laplacian(0, x, y, params)
+            - self.potential(x, y) / params.hbar * u
+    }
+}
+
+impl Solver for CpuSolver {
+    // Each stage only writes the channel that it does not read from neighbouring cells, so both
+    // can update the grid in place just like the shader does.
+    fn step(&mut self, params: &StepParams) {
+        let dt = params.time_step;
+        let (width, height) = (self.grid.width, self.grid.height);
+
+        for y in 0..self.grid.height {
+            for x in 0..self.grid.width {
+                let [u, v, potential, _] = self.grid.get(x, y);
+                let new_v = (v + self.dv_dt(x as i32, y as i32, params) * dt)
+                    * f32::exp(-solver::absorption(x, y, width, height, params) * dt);
+                self.grid.set(x, y, [u, new_v, potential, v]);
+            }
+        }
+
+        for y in 0..self.grid.height {
+            for x in 0..self.grid.width {
+                let [u, v, potential, old_v] = self.grid.get(x, y);
+                let new_u = (u + self.du_dt(x as i32, y as i32, params) * dt)
+                    * f32::exp(-solver::absorption(x, y, width, height, params) * dt);
+                self.grid.set(x, y, [new_u, v, potential, old_v]);
+            }
+        }
+    }
+
+    fn read_grid(&self) -> Grid {
+        self.grid.clone()
+    }
+
+    fn write_grid(&mut self, grid: &Grid) {
+        self.grid = grid.clone();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wave_packet::WavePacket;
+
+    fn params(boundary_condition: BoundaryCondition) -> StepParams {
+        StepParams {
+            boundary_condition,
+            ..Default::default()
+        }
+    }
+
+    /// A 3 by 3 grid with Re(Ψ) = 1 in the top left corner and 0.5 in the top right one
+    fn corners() -> CpuSolver {
+        let mut grid = Grid::new(3, 3);
+        grid.set(0, 0, [1.0, 0.0, 0.0, 0.0]);
+        grid.set(2, 0, [0.5, 0.0, 0.0, 0.0]);
+        CpuSolver::new(grid)
+    }
+
+    #[test]
+    fn dirichlet_stencil_reads_zero_outside() {
+        let solver = corners();
+        let params = params(BoundaryCondition::Dirichlet);
+        // (0 - 2 + 0) + (0 - 2 + 0)
+        assert_eq!(solver.laplacian(0, 0, 0, &params), -4.0);
+        // (1 - 0 + 0.5) + (0 - 0 + 0)
+        assert_eq!(solver.laplacian(0, 1, 0, &params), 1.5);
+        // Absorbing edges read the same way
+        let absorbing = self::params(BoundaryCondition::Absorbing);
+        assert_eq!(solver.laplacian(0, 0, 0, &absorbing), -4.0);
+    }
+
+    #[test]
+    fn neumann_stencil_drops_the_outer_differences() {
+        let solver = corners();
+        let params = params(BoundaryCondition::Neumann);
+        // Only the differences to (1, 0) and (0, 1) remain
+        assert_eq!(solver.laplacian(0, 0, 0, &params), -2.0);
+        assert_eq!(solver.laplacian(0, 2, 0, &params), -1.0);
+        assert_eq!(solver.laplacian(0, 1, 1, &params), 0.0);
+    }
+
+    #[test]
+    fn periodic_stencil_wraps_around() {
+        let solver = corners();
+        let params = params(BoundaryCondition::Periodic);
+        // The left neighbour of (0, 0) is (2, 0): (0.5 - 2 + 0) + (0 - 2 + 0)
+        assert_eq!(solver.laplacian(0, 0, 0, &params), -3.5);
+        // The right neighbour of (2, 0) is (0, 0): (0 - 1 + 1) + (0 - 1 + 0)
+        assert_eq!(solver.laplacian(0, 2, 0, &params), -1.0);
+    }
+
+    #[test]
+    fn stencil_scales_with_cell_spacing() {
+        let solver = corners();
+        let params = StepParams {
+            cell_spacing: 0.5,
+            ..params(BoundaryCondition::Dirichlet)
+        };
+        assert_eq!(solver.laplacian(0, 0, 0, &params), -16.0);
+    }
+
+    #[test]
+    fn single_step_by_hand() {
+        // One cell with Ψ = 1 in a potential of 2, whose Laplacian is -4Ψ at Dirichlet edges
+        let mut grid = Grid::new(1, 1);
+        grid.set(0, 0, [1.0, 0.0, 2.0, 0.0]);
+        let mut solver = CpuSolver::new(grid);
+        let params = StepParams {
+            time_step: 0.1,
+            ..params(BoundaryCondition::Dirichlet)
+        };
+        solver.step(&params);
+
+        // ∂v/∂t = 0.5·(-4·1) - 2·1 = -4, so v = -0.4
+        // ∂u/∂t = -0.5·(-4·-0.4) + 2·-0.4 = -1.6, so u = 0.84
+        let [u, v, potential, old_v] = solver.read_grid().get(0, 0);
+        assert!((u - 0.84).abs() < 1e-6, "u = {}", u);
+        assert!((v + 0.4).abs() < 1e-6, "v = {}", v);
+        assert_eq!(potential, 2.0);
+        assert_eq!(old_v, 0.0);
+    }
+
+    #[test]
+    fn conserves_probability_below_the_stability_limit() {
+        for boundary_condition in [
+            BoundaryCondition::Dirichlet,
+            BoundaryCondition::Neumann,
+            BoundaryCondition::Periodic,
+        ] {
+            let mut grid = Grid::new(32, 32);
+            for (i, cell) in grid.cells.iter_mut().enumerate() {
+                let x = (i % 32) as f32 - 16.0;
+                let y = (i / 32) as f32 - 16.0;
+                cell[2] = 0.005 * (x * x + y * y);
+            }
+            let mut params = params(boundary_condition);
+            params.time_step = 0.8 * params.stability_limit(2.56);
+            WavePacket {
+                x: 14.0,
+                y: 16.0,
+                sigma: 2.0,
+                amplitude: 1.0,
+                theta: 0.5,
+                speed: 0.4,
+            }
+            .apply(&mut grid, &params);
+
+            // |Ψ|² in the grid mixes Re(Ψ) after the step with Im(Ψ) half a step before it, so it
+            // wobbles by O(dt). What leapfrog conserves exactly is Σ Re(Ψ)ₙ·Re(Ψ)ₙ₊₁ + Im(Ψ)ₙ₊½².
+            let mut solver = CpuSolver::new(grid);
+            let mut step = || -> f64 {
+                let before = solver.read_grid();
+                solver.step(&params);
+                let after = solver.read_grid();
+                before
+                    .cells
+                    .iter()
+                    .zip(&after.cells)
+                    .map(|(before, after)| {
+                        before[0] as f64 * after[0] as f64 + (after[1] as f64).powi(2)
+                    })
+                    .sum()
+            };
+            let start = step();
+            let mut end = start;
+            for _ in 0..500 {
+                end = step();
+            }
+            assert!(
+                ((end - start) / start).abs() < 1e-5,
+                "{:?}: {} became {}",
+                boundary_condition,
+                start,
+                end
+            );
+        }
+    }
+}
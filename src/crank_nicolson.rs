@@ -0,0 +1,372 @@
+use crate::complex::Complex;
+use crate::grid::Grid;
+use crate::solver::{self, Solver, StepParams};
+use crate::ui_state::BoundaryCondition;
+
+/// An unconditionally stable Crank–Nicolson integrator, split into alternating directions.
+///
+/// The Hamiltonian `H = -ħ²/2m·∇² + V - iħW`, where `W` is the absorbing layer, is split into its
+/// row and column parts, each taking half of the potential. Each step applies the Crank–Nicolson
+/// (Cayley) operator `(1 + i·τ/2ħ·H)⁻¹(1 - i·τ/2ħ·H)` of the row part for half a step, of the
+/// column part for a whole step and of the row part again for the other half, so that every
+/// sweep only needs tridiagonal solves along single lines of cells. Each of these operators is
+/// unitary, so apart from the absorbing layer the norm is preserved at any time step.
+///
+/// Unlike the leapfrog kernel, Re(Ψ) and Im(Ψ) are at the same point in time after every step,
+/// so the previous Im(Ψ) is simply set to the current one.
+pub struct CrankNicolsonSolver {
+    grid: Grid,
+}
+
+impl CrankNicolsonSolver {
+    pub fn new(grid: Grid) -> Self {
+        CrankNicolsonSolver { grid }
+    }
+
+    /// Applies the Crank–Nicolson operator for `time_step` along every row, or every column if
+    /// `columns` is set.
+    fn sweep(&mut self, params: &StepParams, time_step: f32, columns: bool) {
+        let (width, height) = (self.grid.width, self.grid.height);
+        let (length, lines) = if columns {
+            (height, width)
+        } else {
+            (width, height)
+        };
+        let cell = |line: u32, i: u32| if columns { (line, i) } else { (i, line) };
+
+        let hbar = params.hbar as f64;
+        let a = time_step as f64 / (2.0 * hbar);
+        let coupling =
+            -hbar * params.kinetic_coefficient() as f64 / (params.cell_spacing as f64).powi(2);
+        let bc = params.boundary_condition;
+        let periodic = bc == BoundaryCondition::Periodic;
+        let n = length as usize;
+
+        let mut psi = vec![Complex::ZERO; n];
+        let mut h_diagonal = vec![Complex::ZERO; n];
+        let mut lower = vec![Complex::ZERO; n];
+        let mut upper = vec![Complex::ZERO; n];
+
+        for line in 0..lines {
+            for i in 0..length {
+                let (x, y) = cell(line, i);
+                let [u, v, potential, _] = self.grid.get(x, y);
+                psi[i as usize] = Complex::new(u as f64, v as f64);
+
+                // Half of the potential and absorption belongs to each direction
+                let absorption = solver::absorption(x, y, width, height, params) as f64 * hbar;
+                h_diagonal[i as usize] = Complex::new(potential as f64, -absorption) * 0.5;
+            }
+
+            // The coupling of each cell to its neighbours along the line. Neumann edges have no
+            // flux through them, Dirichlet and absorbing edges couple to a zero outside.
+            for i in 0..n {
+                let has_lower = i > 0 || periodic;
+                let has_upper = i < n - 1 || periodic;
+                let counted = match bc {
+                    BoundaryCondition::Neumann => has_lower as u32 + has_upper as u32,
+                    _ => 2,
+                };
+                h_diagonal[i] += Complex::new(-coupling * counted as f64, 0.0);
+                lower[i] = Complex::new(if has_lower { coupling } else { 0.0 }, 0.0);
+                upper[i] = Complex::new(if has_upper { coupling } else { 0.0 }, 0.0);
+            }
+
+            // rhs = (1 - i·a·H)ψ
+            let neighbour = |i: usize, offset: isize| {
+                psi[(i as isize + offset).rem_euclid(n as isize) as usize]
+            };
+            let rhs = (0..n)
+                .map(|i| {
+                    let h_psi = h_diagonal[i] * psi[i]
+                        + lower[i] * neighbour(i, -1)
+                        + upper[i] * neighbour(i, 1);
+                    psi[i] - Complex::I * h_psi * a
+                })
+                .collect::<Vec<_>>();
+
+            // (1 + i·a·H)ψ' = rhs
+            let diagonal = h_diagonal
+                .iter()
+                .map(|&h| Complex::ONE + Complex::I * h * a)
+                .collect::<Vec<_>>();
+            let matrix_lower = lower
+                .iter()
+                .map(|&c| Complex::I * c * a)
+                .collect::<Vec<_>>();
+            let matrix_upper = upper
+                .iter()
+                .map(|&c| Complex::I * c * a)
+                .collect::<Vec<_>>();
+            let result = solve_line(&matrix_lower, &diagonal, &matrix_upper, rhs, periodic);
+
+            for (i, value) in result.iter().enumerate() {
+                let (x, y) = cell(line, i as u32);
+                let potential = self.grid.get(x, y)[2];
+                let (u, v) = (value.re as f32, value.im as f32);
+                self.grid.set(x, y, [u, v, potential, v]);
+            }
+        }
+    }
+}
+
+impl Solver for CrankNicolsonSolver {
+    fn step(&mut self, params: &StepParams) {
+        let dt = params.time_step;
+        self.sweep(params, dt / 2.0, false);
+        self.sweep(params, dt, true);
+        self.sweep(params, dt / 2.0, false);
+    }
+
+    fn read_grid(&self) -> Grid {
+        self.grid.clone()
+    }
+
+    fn write_grid(&mut self, grid: &Grid) {
+        self.grid = grid.clone();
+    }
+}
+
+/// Solves the tridiagonal system with the given diagonals. `lower[0]` and `upper[n - 1]` are the
+/// corners of the matrix when it is `cyclic`, and are ignored otherwise.
+fn solve_line(
+    lower: &[Complex],
+    diagonal: &[Complex],
+    upper: &[Complex],
+    rhs: Vec<Complex>,
+    cyclic: bool,
+) -> Vec<Complex> {
+    let n = diagonal.len();
+    match n {
+        1 => {
+            // A single cell is its own neighbour on both sides when it wraps around
+            let mut diagonal = diagonal[0];
+            if cyclic {
+                diagonal = diagonal + lower[0] + upper[0];
+            }
+            vec![rhs[0] / diagonal]
+        }
+        2 if cyclic => {
+            // Both neighbours of each cell are the other cell
+            let (a, b) = (diagonal[0], upper[0] + lower[0]);
+            let (c, d) = (upper[1] + lower[1], diagonal[1]);
+            let determinant = a * d - b * c;
+            vec![
+                (d * rhs[0] - b * rhs[1]) / determinant,
+                (a * rhs[1] - c * rhs[0]) / determinant,
+            ]
+        }
+        _ if cyclic => solve_cyclic(lower, diagonal, upper, rhs),
+        _ => solve_tridiagonal(lower, diagonal, upper, rhs),
+    }
+}
+
+/// The Thomas algorithm, `lower[0]` and `upper[n - 1]` are unused.
+fn solve_tridiagonal(
+    lower: &[Complex],
+    diagonal: &[Complex],
+    upper: &[Complex],
+    mut rhs: Vec<Complex>,
+) -> Vec<Complex> {
+    let n = diagonal.len();
+    let mut upper_prime = vec![Complex::ZERO; n];
+
+    upper_prime[0] = upper[0] / diagonal[0];
+    rhs[0] = rhs[0] / diagonal[0];
+    for i in 1..n {
+        let m = diagonal[i] - lower[i] * upper_prime[i - 1];
+        upper_prime[i] = upper[i] / m;
+        rhs[i] = (rhs[i] - lower[i] * rhs[i - 1]) / m;
+    }
+
+    for i in (0..n - 1).rev() {
+        rhs[i] = rhs[i] - upper_prime[i] * rhs[i + 1];
+    }
+
+    rhs
+}
+
+/// Solves a cyclic tridiagonal system with the Sherman–Morrison formula, treating the corners as
+/// a rank one correction to a plain tridiagonal system.
+fn solve_cyclic(
+    lower: &[Complex],
+    diagonal: &[Complex],
+    upper: &[Complex],
+    rhs: Vec<Complex>,
+) -> Vec<Complex> {
+    let n = diagonal.len();
+    let top_right = lower[0];
+    let bottom_left = upper[n - 1];
+
+    let gamma = -diagonal[0];
+    let mut modified = diagonal.to_vec();
+    modified[0] = diagonal[0] - gamma;
+    modified[n - 1] = diagonal[n - 1] - top_right * bottom_left / gamma;
+
+    let x = solve_tridiagonal(lower, &modified, upper, rhs);
+
+    let mut correction = vec![Complex::ZERO; n];
+    correction[0] = gamma;
+    correction[n - 1] = bottom_left;
+    let z = solve_tridiagonal(lower, &modified, upper, correction);
+
+    let factor = (x[0] + top_right * x[n - 1] / gamma)
+        / (Complex::ONE + z[0] + top_right * z[n - 1] / gamma);
+    x.iter().zip(&z).map(|(&x, &z)| x - z * factor).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cpu_solver::CpuSolver;
+    use crate::wave_packet::WavePacket;
+
+    /// Deterministic complex values in about -1..1
+    fn values(n: usize, seed: f64) -> Vec<Complex> {
+        (0..n)
+            .map(|i| {
+                let i = i as f64 + seed;
+                Complex::new((1.7 * i).sin(), (0.9 * i).cos())
+            })
+            .collect()
+    }
+
+    /// Multiplies `x` by the matrix that `solve_line` inverts
+    fn multiply(
+        lower: &[Complex],
+        diagonal: &[Complex],
+        upper: &[Complex],
+        x: &[Complex],
+        cyclic: bool,
+    ) -> Vec<Complex> {
+        let n = x.len();
+        (0..n)
+            .map(|i| {
+                let mut y = diagonal[i] * x[i];
+                if i > 0 || cyclic {
+                    y += lower[i] * x[(i + n - 1) % n];
+                }
+                if i < n - 1 || cyclic {
+                    y += upper[i] * x[(i + 1) % n];
+                }
+                y
+            })
+            .collect()
+    }
+
+    fn check_solve(n: usize, cyclic: bool) {
+        let lower = values(n, 0.3);
+        let upper = values(n, 5.1);
+        // Diagonally dominant, like the Crank–Nicolson systems
+        let diagonal = values(n, 2.2)
+            .into_iter()
+            .map(|z| z + Complex::new(4.0, 1.0))
+            .collect::<Vec<_>>();
+        let rhs = values(n, 7.7);
+
+        let x = solve_line(&lower, &diagonal, &upper, rhs.clone(), cyclic);
+        let product = multiply(&lower, &diagonal, &upper, &x, cyclic);
+        for (i, (a, b)) in product.iter().zip(&rhs).enumerate() {
+            assert!(
+                (*a - *b).norm_sqr().sqrt() < 1e-12,
+                "n = {}, cyclic = {}, row {}: {:?} != {:?}",
+                n,
+                cyclic,
+                i,
+                a,
+                b
+            );
+        }
+    }
+
+    #[test]
+    fn solves_tridiagonal_systems() {
+        for n in [1, 2, 3, 8] {
+            check_solve(n, false);
+        }
+    }
+
+    #[test]
+    fn solves_cyclic_systems() {
+        for n in [1, 2, 3, 8] {
+            check_solve(n, true);
+        }
+    }
+
+    fn packet_grid(params: &StepParams) -> Grid {
+        let mut grid = Grid::new(32, 24);
+        for (i, cell) in grid.cells.iter_mut().enumerate() {
+            let x = (i % 32) as f32 - 16.0;
+            cell[2] = 0.01 * x * x;
+        }
+        WavePacket {
+            x: 14.0,
+            y: 12.0,
+            sigma: 2.0,
+            amplitude: 1.0,
+            theta: 0.5,
+            speed: 0.4,
+        }
+        .apply(&mut grid, params);
+        grid
+    }
+
+    fn total(grid: &Grid) -> f64 {
+        (0..grid.cells.len())
+            .map(|i| grid.probability(i) as f64)
+            .sum()
+    }
+
+    #[test]
+    fn preserves_the_norm_above_the_leapfrog_limit() {
+        for boundary_condition in [
+            BoundaryCondition::Dirichlet,
+            BoundaryCondition::Neumann,
+            BoundaryCondition::Periodic,
+        ] {
+            let mut params = StepParams {
+                boundary_condition,
+                ..Default::default()
+            };
+            params.time_step = 20.0 * params.stability_limit(2.56);
+            let mut solver = CrankNicolsonSolver::new(packet_grid(&params));
+            let start = total(&solver.read_grid());
+            for _ in 0..200 {
+                solver.step(&params);
+            }
+            let end = total(&solver.read_grid());
+            assert!(
+                ((end - start) / start).abs() < 1e-6,
+                "{:?}: {} became {}",
+                boundary_condition,
+                start,
+                end
+            );
+        }
+    }
+
+    #[test]
+    fn agrees_with_leapfrog_at_small_time_steps() {
+        let params = StepParams {
+            time_step: 0.01,
+            boundary_condition: BoundaryCondition::Dirichlet,
+            ..Default::default()
+        };
+        let grid = packet_grid(&params);
+        let mut leapfrog = CpuSolver::new(grid.clone());
+        let mut crank_nicolson = CrankNicolsonSolver::new(grid);
+        for _ in 0..500 {
+            leapfrog.step(&params);
+            crank_nicolson.step(&params);
+        }
+
+        let (a, b) = (leapfrog.read_grid(), crank_nicolson.read_grid());
+        let peak = (0..a.cells.len())
+            .map(|i| a.probability(i))
+            .fold(0.0, f32::max);
+        let difference = (0..a.cells.len())
+            .map(|i| (a.probability(i) - b.probability(i)).abs())
+            .fold(0.0, f32::max);
+        assert!(difference < 0.01 * peak, "{} of {}", difference, peak);
+    }
+}
@@ -0,0 +1,174 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::grid::Grid;
+use crate::solver::StepParams;
+use crate::ui_state::BoundaryCondition;
+
+/// The most detectors that `detectors.glsl` measures at once, which must match `MAX_DETECTORS`
+/// there.
+pub const MAX_DETECTORS: usize = 8;
+
+/// The most corners a detector can have, which must match `MAX_VERTICES` in `detectors.glsl`.
+pub const MAX_VERTICES: usize = 16;
+
+/// The number of vec4s each detector takes up in the buffer for `detectors.glsl`: its number of
+/// corners followed by two corners per vec4.
+pub const DETECTOR_STRIDE: usize = 1 + MAX_VERTICES / 2;
+
+/// A region of the grid that measures how much probability is inside it and how fast it flows
+/// out through its boundary.
+///
+/// The region is a polygon with its corners in cell coordinates, measured from the centre of the
+/// top left cell like the brush is. A cell belongs to the region if its centre is inside the
+/// polygon.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Detector {
+    pub name: String,
+    pub vertices: Vec<(f32, f32)>,
+}
+
+impl Detector {
+    /// A rectangle that covers every cell from (`x0`, `y0`) to (`x1`, `y1`), in either order.
+    pub fn rectangle(name: String, (x0, y0): (i32, i32), (x1, y1): (i32, i32)) -> Self {
+        let (left, right) = (x0.min(x1) as f32 - 0.5, x0.max(x1) as f32 + 0.5);
+        let (top, bottom) = (y0.min(y1) as f32 - 0.5, y0.max(y1) as f32 + 0.5);
+        Detector {
+            name,
+            vertices: vec![(left, top), (right, top), (right, bottom), (left, bottom)],
+        }
+    }
+
+    /// Whether the point is inside the polygon, by the even-odd rule like `detectors.glsl`.
+    pub fn contains(&self, x: f32, y: f32) -> bool {
+        let mut inside = false;
+        let n = self.vertices.len();
+        for i in 0..n {
+            let (ax, ay) = self.vertices[i];
+            let (bx, by) = self.vertices[(i + 1) % n];
+            if (ay > y) != (by > y) && x < ax + (y - ay) / (by - ay) * (bx - ax) {
+                inside = !inside;
+            }
+        }
+        inside
+    }
+}
+
+/// Packs `detectors` for `detectors.glsl`, [`DETECTOR_STRIDE`] vec4s each. There is always at
+/// least one detector's worth, since a storage buffer cannot be empty.
+pub fn pack(detectors: &[Detector]) -> Vec<[f32; 4]> {
+    let mut packed = Vec::with_capacity(detectors.len().max(1) * DETECTOR_STRIDE);
+    for detector in detectors {
+        let mut corners = [0.0; MAX_VERTICES * 2];
+        for (i, &(x, y)) in detector.vertices.iter().take(MAX_VERTICES).enumerate() {
+            corners[2 * i] = x;
+            corners[2 * i + 1] = y;
+        }
+        packed.push([
+            detector.vertices.len().min(MAX_VERTICES) as f32,
+            0.0,
+            0.0,
+            0.0,
+        ]);
+        packed.extend(corners.chunks_exact(4).map(|c| [c[0], c[1], c[2], c[3]]));
+    }
+    if packed.is_empty() {
+        packed.resize(DETECTOR_STRIDE, [0.0; 4]);
+    }
+    packed
+}
+
+/// What one detector measured at the end of a step.
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub struct Reading {
+    /// `Σ|Ψ|²` over the cells inside, with |Ψ|² from [`Grid::probability`] like
+    /// `Observables::total_probability`.
+    pub probability: f64,
+    /// The rate at which probability leaves through the boundary, negative while it flows in.
+    pub flux: f64,
+    /// The flux integrated over every step since the detectors were last reset, the net
+    /// probability that has left.
+    pub outflow: f64,
+}
+
+/// The readings of every detector at one point in time.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DetectorSample {
+    pub time: f64,
+    pub readings: Vec<Reading>,
+}
+
+/// Measures every detector in `grid` on the CPU, the same way `detectors.glsl` does, leaving the
+/// outflow at 0.
+///
+/// The flux between a cell inside and a neighbour outside is `(ħ/m·dx²)·Im(Ψ*·Ψₙ)`, the rate at
+/// which the 5-point Laplacian moves `|Ψ|²` from one to the other, so the probability inside
+/// changes by exactly the flux out unless the potential is absorbing. Neighbours outside of the
+/// grid follow the boundary condition, and only periodic edges carry any flux.
+pub fn measure(grid: &Grid, detectors: &[Detector], params: &StepParams) -> Vec<Reading> {
+    let (width, height) = (grid.width as i64, grid.height as i64);
+    let periodic = params.boundary_condition == BoundaryCondition::Periodic;
+    let coupling = params.hbar as f64 / (params.mass as f64 * (params.cell_spacing as f64).powi(2));
+
+    detectors
+        .iter()
+        .map(|detector| {
+            let mut reading = Reading::default();
+            for y in 0..height {
+                for x in 0..width {
+                    if !detector.contains(x as f32, y as f32) {
+                        continue;
+                    }
+                    let i = (y * width + x) as usize;
+                    let [u, v, _, _] = grid.cells[i];
+                    reading.probability += grid.probability(i) as f64;
+
+                    for (nx, ny) in [(x - 1, y), (x + 1, y), (x, y - 1), (x, y + 1)] {
+                        let (nx, ny) = if periodic {
+                            (nx.rem_euclid(width), ny.rem_euclid(height))
+                        } else if nx < 0 || nx >= width || ny < 0 || ny >= height {
+                            continue;
+                        } else {
+                            (nx, ny)
+                        };
+                        if detector.contains(nx as f32, ny as f32) {
+                            continue;
+                        }
+                        let [nu, nv, _, _] = grid.cells[(ny * width + nx) as usize];
+                        reading.flux += coupling * (u as f64 * nv as f64 - v as f64 * nu as f64);
+                    }
+                }
+            }
+            reading
+        })
+        .collect()
+}
+
+/// Writes the history of every detector as CSV, one row per sample with the time followed by the
+/// probability, the flux and the outflow of each detector.
+pub fn export_csv<'a>(
+    path: &Path,
+    detectors: &[Detector],
+    history: impl IntoIterator<Item = &'a DetectorSample>,
+) -> io::Result<()> {
+    let mut csv = String::from("time");
+    for detector in detectors {
+        let name = detector.name.replace(',', " ");
+        csv += &format!(",{name} probability,{name} flux,{name} outflow");
+    }
+    csv.push('\n');
+
+    for sample in history {
+        csv += &sample.time.to_string();
+        for reading in &sample.readings {
+            csv += &format!(
+                ",{},{},{}",
+                reading.probability, reading.flux, reading.outflow
+            );
+        }
+        csv.push('\n');
+    }
+
+    fs::write(path, csv)
+}
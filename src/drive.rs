@@ -0,0 +1,282 @@
+use crate::expression::PotentialExpression;
+use crate::grid::Grid;
+use crate::scene::PotentialShape;
+use crate::solver::{Solver, StepParams};
+
+/// The number of `vec4`s each driven potential shape takes up in the buffer read by
+/// `drives.glsl`.
+pub const DRIVE_STRIDE: usize = 5;
+
+/// The kind in the header of a packed expression, after those of the potential shapes.
+const EXPRESSION_KIND: f32 = 5.0;
+
+/// How a potential shape changes over time. Its strength is scaled by
+/// `mean + oscillation·sin(ωt + φ)`, optionally under a Gaussian pulse, and it is moved by
+/// `velocity·t + swing·sin(ωt + φ)`.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Drive {
+    pub mean: f32,
+    pub oscillation: f32,
+    /// The angular frequency ω of the oscillation and the swing.
+    pub omega: f32,
+    /// The phase φ of the oscillation and the swing.
+    pub phase: f32,
+    /// The time at which the pulse peaks and its standard deviation, or `None` for a drive that
+    /// is always on.
+    pub pulse: Option<(f32, f32)>,
+    pub velocity: (f32, f32),
+    pub swing: (f32, f32),
+}
+
+impl Default for Drive {
+    fn default() -> Self {
+        Drive {
+            mean: 1.0,
+            oscillation: 0.0,
+            omega: 0.0,
+            phase: 0.0,
+            pulse: None,
+            velocity: (0.0, 0.0),
+            swing: (0.0, 0.0),
+        }
+    }
+}
+
+impl Drive {
+    /// The factor that the shape is scaled by at time `t`.
+    pub fn strength(&self, t: f64) -> f64 {
+        let oscillation =
+            self.oscillation as f64 * (self.omega as f64 * t + self.phase as f64).sin();
+        let envelope = match self.pulse {
+            Some((center, width)) => (-0.5 * ((t - center as f64) / width as f64).powi(2)).exp(),
+            None => 1.0,
+        };
+        (self.mean as f64 + oscillation) * envelope
+    }
+
+    /// How far the shape has moved from where it started at time `t`.
+    pub fn offset(&self, t: f64) -> (f64, f64) {
+        let swing = (self.omega as f64 * t + self.phase as f64).sin();
+        (
+            self.velocity.0 as f64 * t + self.swing.0 as f64 * swing,
+            self.velocity.1 as f64 * t + self.swing.1 as f64 * swing,
+        )
+    }
+}
+
+/// A potential that changes over time, which is added to the static potential of the grid at
+/// every step instead of being drawn onto it.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DrivenPotential {
+    /// A potential shape that is scaled and moved by a drive.
+    Shape { shape: PotentialShape, drive: Drive },
+    /// An expression that depends on `t`.
+    Expression(PotentialExpression),
+}
+
+impl DrivenPotential {
+    /// The potential at the cell `(x, y)` of a grid `height` cells tall at time `t`.
+    pub fn value(&self, x: f32, y: f32, height: f32, t: f64) -> f32 {
+        match self {
+            DrivenPotential::Shape { shape, drive } => {
+                let (dx, dy) = drive.offset(t);
+                let value = shape.value(x - dx as f32, y - dy as f32, height);
+                (value as f64 * drive.strength(t)) as f32
+            }
+            DrivenPotential::Expression(expression) => expression.value(x, y, t),
+        }
+    }
+
+    /// A bound on `|V|` over a `width` by `height` grid, taken where the potential starts. Shapes
+    /// that move or pulse stay under it, except for harmonic traps and fields moved far enough
+    /// that their steepest part reaches further into the grid. Expressions are only evaluated at
+    /// `t = 0`.
+    pub fn max_value(&self, width: u32, height: u32) -> f32 {
+        let largest = (0..height)
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
+            .map(|(x, y)| match self {
+                DrivenPotential::Shape { shape, .. } => {
+                    shape.value(x as f32, y as f32, height as f32).abs()
+                }
+                DrivenPotential::Expression(expression) => {
+                    expression.value(x as f32, y as f32, 0.0).abs()
+                }
+            })
+            .fold(0.0, f32::max);
+        match self {
+            DrivenPotential::Shape { drive, .. } => {
+                largest * (drive.mean.abs() + drive.oscillation.abs())
+            }
+            DrivenPotential::Expression(_) => largest,
+        }
+    }
+
+    /// Packs the potential in the layout that `driven_potential` in `drives.glsl` reads,
+    /// resolving any default that depends on the height of the grid. Shapes take up
+    /// [`DRIVE_STRIDE`] `vec4`s, and expressions a header with their length followed by the
+    /// instructions from [`PotentialExpression::pack`].
+    pub fn pack(&self, grid_height: u32) -> Vec<[f32; 4]> {
+        let (shape, drive) = match self {
+            DrivenPotential::Shape { shape, drive } => (*shape, drive),
+            DrivenPotential::Expression(expression) => {
+                let packed = expression.pack();
+                let header = [EXPRESSION_KIND, (packed.len() - 1) as f32, 0.0, 0.0];
+                return [header].into_iter().chain(packed).collect();
+            }
+        };
+
+        let (kind, parameters) = match shape {
+            PotentialShape::Rectangle {
+                x,
+                y,
+                width,
+                height,
+                value,
+            } => (0.0, [x, y, width, height, value, 0.0, 0.0]),
+            PotentialShape::Circle {
+                x,
+                y,
+                radius,
+                value,
+            } => (1.0, [x, y, radius, value, 0.0, 0.0, 0.0]),
+            PotentialShape::Slits {
+                x,
+                thickness,
+                value,
+                count,
+                slit_width,
+                slit_spacing,
+                center,
+            } => (
+                2.0,
+                [
+                    x,
+                    thickness,
+                    value,
+                    count as f32,
+                    slit_width,
+                    slit_spacing,
+                    center.unwrap_or(grid_height as f32 / 2.0),
+                ],
+            ),
+            PotentialShape::Harmonic { x, y, k } => (3.0, [x, y, k, 0.0, 0.0, 0.0, 0.0]),
+            PotentialShape::Field {
+                x,
+                y,
+                force_x,
+                force_y,
+            } => (4.0, [x, y, force_x, force_y, 0.0, 0.0, 0.0]),
+        };
+        let p = parameters;
+        let (pulse_center, pulse_width) = drive.pulse.unwrap_or((0.0, 0.0));
+
+        vec![
+            [kind, p[0], p[1], p[2]],
+            [p[3], p[4], p[5], p[6]],
+            [drive.mean, drive.oscillation, drive.omega, drive.phase],
+            [
+                pulse_center,
+                pulse_width,
+                drive.velocity.0,
+                drive.velocity.1,
+            ],
+            [drive.swing.0, drive.swing.1, 0.0, 0.0],
+        ]
+    }
+}
+
+/// Packs every driven potential for `drives.glsl`. Storage buffers cannot be empty, so without any
+/// drives this is a single zeroed one that is never read.
+pub fn pack(drives: &[DrivenPotential], grid_height: u32) -> Vec<[f32; 4]> {
+    if drives.is_empty() {
+        return vec![[0.0; 4]; DRIVE_STRIDE];
+    }
+    drives
+        .iter()
+        .flat_map(|drive| drive.pack(grid_height))
+        .collect()
+}
+
+/// Adds the potential of every drive at time `t` to the potential channel of `grid`.
+pub fn add_potentials(grid: &mut Grid, drives: &[DrivenPotential], t: f64) {
+    if drives.is_empty() {
+        return;
+    }
+
+    let height = grid.height as f32;
+    for y in 0..grid.height {
+        for x in 0..grid.width {
+            let index = grid.index(x, y);
+            grid.cells[index][2] += drives
+                .iter()
+                .map(|drive| drive.value(x as f32, y as f32, height, t))
+                .sum::<f32>();
+        }
+    }
+}
+
+/// Runs a CPU solver with driven potentials added to its grid's static potential for each step,
+/// evaluated halfway through the step like the leapfrog kernel does. The grids it reads and
+/// writes only hold the static potential.
+pub struct DrivenSolver {
+    solver: Box<dyn Solver>,
+    drives: Vec<DrivenPotential>,
+    /// The potential channel without the drives.
+    potential: Vec<f32>,
+    time: f64,
+}
+
+impl DrivenSolver {
+    /// Wraps `solver`, whose grid is taken to be at simulation time `time`.
+    pub fn new(solver: Box<dyn Solver>, drives: Vec<DrivenPotential>, time: f64) -> Self {
+        let potential = solver
+            .read_grid()
+            .cells
+            .iter()
+            .map(|cell| cell[2])
+            .collect();
+        DrivenSolver {
+            solver,
+            drives,
+            potential,
+            time,
+        }
+    }
+
+    /// Wraps `solver` only if there are any drives.
+    pub fn wrap(solver: Box<dyn Solver>, drives: &[DrivenPotential], time: f64) -> Box<dyn Solver> {
+        if drives.is_empty() {
+            solver
+        } else {
+            Box::new(DrivenSolver::new(solver, drives.to_vec(), time))
+        }
+    }
+}
+
+impl Solver for DrivenSolver {
+    fn step(&mut self, params: &StepParams) {
+        let mut grid = self.read_grid();
+        add_potentials(
+            &mut grid,
+            &self.drives,
+            self.time + params.time_step as f64 / 2.0,
+        );
+        self.solver.write_grid(&grid);
+
+        self.solver.step(params);
+        self.time += params.time_step as f64;
+    }
+
+    fn read_grid(&self) -> Grid {
+        let mut grid = self.solver.read_grid();
+        for (cell, &potential) in grid.cells.iter_mut().zip(&self.potential) {
+            cell[2] = potential;
+        }
+        grid
+    }
+
+    fn write_grid(&mut self, grid: &Grid) {
+        self.potential = grid.cells.iter().map(|cell| cell[2]).collect();
+        self.solver.write_grid(grid);
+    }
+}
@@ -0,0 +1,304 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::complex::Complex;
+use crate::grid::Grid;
+use crate::random;
+use crate::solver::StepParams;
+use crate::state_file::SimulationState;
+use crate::ui_state::BoundaryCondition;
+
+/// The total probability given to the starting guess when the grid holds no wave function, about
+/// that of a packet drawn with the default brush.
+const DEFAULT_PROBABILITY: f64 = 400.0;
+
+/// A stationary state of the potential, found by [`EigenstateSearch`].
+#[derive(Debug, Clone)]
+pub struct Eigenstate {
+    pub energy: f64,
+    /// `‖Hψ - Eψ‖ / ‖ψ‖` when the state was accepted.
+    pub residual: f64,
+    pub iterations: u64,
+    /// The state in the grid layout, along with the potential it belongs to.
+    pub grid: Grid,
+}
+
+/// Finds the lowest eigenstates of the potential by propagating in imaginary time.
+///
+/// Replacing `t` with `-iτ` turns the Schrödinger equation into `∂ψ/∂τ = -Hψ`, which damps every
+/// eigenstate by `e^(-Eτ)`, so after renormalising only the lowest one is left. Once a state has
+/// converged it is kept and the search starts over from a fresh guess, projecting every state
+/// found so far out of it after each step (Gram–Schmidt) so that it converges to the lowest state
+/// above them instead.
+///
+/// The steps are explicit Euler steps of the same 5-point Hamiltonian that the leapfrog kernel
+/// uses, so the states it finds are stationary there too. The absorbing layer is left out, its
+/// edges act like Dirichlet ones.
+pub struct EigenstateSearch {
+    /// How many states to find.
+    pub count: usize,
+    /// The largest residual, relative to the energy scale of the state, at which a state counts
+    /// as converged.
+    pub tolerance: f64,
+    pub states: Vec<Eigenstate>,
+
+    template: Grid,
+    psi: Vec<Complex>,
+    /// The found states normalised to 1, for the projections.
+    basis: Vec<Vec<Complex>>,
+    /// `‖ψ‖`, which every guess is scaled back to after each step.
+    norm: f64,
+    energy: f64,
+    residual: f64,
+    iterations: u64,
+}
+
+impl EigenstateSearch {
+    /// Starts a search for the lowest `count` states of the potential in `grid`. The wave function
+    /// in `grid` is the first guess, unless there is none.
+    pub fn new(grid: Grid, count: usize) -> Self {
+        let psi = grid
+            .cells
+            .iter()
+            .map(|&[u, v, _, _]| Complex::new(u as f64, v as f64))
+            .collect::<Vec<_>>();
+        let norm = psi.iter().map(|z| z.norm_sqr()).sum::<f64>().sqrt();
+
+        let mut search = EigenstateSearch {
+            count,
+            tolerance: 1e-3,
+            states: Vec::new(),
+
+            template: grid,
+            psi,
+            basis: Vec::new(),
+            norm,
+            energy: 0.0,
+            residual: f64::INFINITY,
+            iterations: 0,
+        };
+        if norm == 0.0 {
+            search.norm = DEFAULT_PROBABILITY.sqrt();
+            search.reseed();
+        }
+
+        search
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.states.len() >= self.count
+    }
+
+    /// The energy of the current guess at the last step.
+    pub fn energy(&self) -> f64 {
+        self.energy
+    }
+
+    /// `‖Hψ - Eψ‖ / ‖ψ‖` of the current guess at the last step, which is 0 for an eigenstate.
+    pub fn residual(&self) -> f64 {
+        self.residual
+    }
+
+    /// The number of steps taken since the current guess was started.
+    pub fn iterations(&self) -> u64 {
+        self.iterations
+    }
+
+    /// The current guess in the grid layout.
+    pub fn candidate(&self) -> Grid {
+        let mut grid = self.template.clone();
+        for (cell, z) in grid.cells.iter_mut().zip(&self.psi) {
+            let (u, v) = (z.re as f32, z.im as f32);
+            *cell = [u, v, cell[2], v];
+        }
+        grid
+    }
+
+    /// Takes up to `iterations` steps, stopping early if the current guess converges. Returns
+    /// whether it did, in which case it has been added to [`Self::states`].
+    pub fn iterate(&mut self, params: &StepParams, iterations: u32) -> bool {
+        if self.is_finished() {
+            return false;
+        }
+
+        let time_step = self.time_step(params);
+        let scale = self.energy_scale(params);
+        for _ in 0..iterations {
+            let h_psi = self.hamiltonian(params);
+
+            let norm_sqr = self.psi.iter().map(|z| z.norm_sqr()).sum::<f64>();
+            self.energy = self
+                .psi
+                .iter()
+                .zip(&h_psi)
+                .map(|(&psi, &h_psi)| (psi.conj() * h_psi).re)
+                .sum::<f64>()
+                / norm_sqr;
+            self.residual = (self
+                .psi
+                .iter()
+                .zip(&h_psi)
+                .map(|(&psi, &h_psi)| (h_psi - psi * self.energy).norm_sqr())
+                .sum::<f64>()
+                / norm_sqr)
+                .sqrt();
+
+            if self.residual <= self.tolerance * (self.energy.abs() + scale) {
+                self.accept();
+                return true;
+            }
+
+            for (psi, &h_psi) in self.psi.iter_mut().zip(&h_psi) {
+                *psi = *psi - h_psi * time_step;
+            }
+            self.orthonormalize();
+            self.iterations += 1;
+        }
+
+        false
+    }
+
+    /// Keeps the current guess as the next eigenstate, converged or not, and starts on the next
+    /// one from a fresh guess.
+    pub fn accept(&mut self) {
+        self.orthonormalize();
+        self.states.push(Eigenstate {
+            energy: self.energy,
+            residual: self.residual,
+            iterations: self.iterations,
+            grid: self.candidate(),
+        });
+
+        let scale = 1.0 / self.norm;
+        self.basis
+            .push(self.psi.iter().map(|&z| z * scale).collect());
+        self.reseed();
+    }
+
+    /// Replaces the guess with noise, which overlaps every state whatever its symmetry.
+    fn reseed(&mut self) {
+        let seed = self.states.len() as u64;
+        for (i, psi) in self.psi.iter_mut().enumerate() {
+            *psi = Complex::new(noise(i as u64, seed), 0.0);
+        }
+        self.orthonormalize();
+        self.energy = 0.0;
+        self.residual = f64::INFINITY;
+        self.iterations = 0;
+    }
+
+    /// Projects the found states out of the guess and scales it back to the norm it started with.
+    fn orthonormalize(&mut self) {
+        for state in &self.basis {
+            let overlap = state
+                .iter()
+                .zip(&self.psi)
+                .fold(Complex::ZERO, |sum, (&state, &psi)| {
+                    sum + state.conj() * psi
+                });
+            for (psi, &state) in self.psi.iter_mut().zip(state) {
+                *psi = *psi - state * overlap;
+            }
+        }
+
+        let norm = self.psi.iter().map(|z| z.norm_sqr()).sum::<f64>().sqrt();
+        if norm > 0.0 {
+            let scale = self.norm / norm;
+            self.psi.iter_mut().for_each(|z| *z = *z * scale);
+        }
+    }
+
+    /// `Hψ = -ħ²/2m·∇²ψ + Vψ`, with the edges handled like in `laplacian` in `compute.glsl`.
+    fn hamiltonian(&self, params: &StepParams) -> Vec<Complex> {
+        let (width, height) = (self.template.width as i64, self.template.height as i64);
+        let bc = params.boundary_condition;
+        // ħ²/2m over dx², the coupling of each cell to its neighbours
+        let coupling = (params.hbar * params.kinetic_coefficient()) as f64
+            / (params.cell_spacing as f64).powi(2);
+
+        // Neumann edges have no flux through them, which is the same as a neighbour outside that
+        // equals the cell itself
+        let at = |x: i64, y: i64, centre: Complex| -> Complex {
+            if bc == BoundaryCondition::Periodic {
+                return self.psi[(y.rem_euclid(height) * width + x.rem_euclid(width)) as usize];
+            }
+            if x < 0 || x >= width || y < 0 || y >= height {
+                return if bc == BoundaryCondition::Neumann {
+                    centre
+                } else {
+                    Complex::ZERO
+                };
+            }
+            self.psi[(y * width + x) as usize]
+        };
+
+        let mut h_psi = vec![Complex::ZERO; self.psi.len()];
+        for y in 0..height {
+            for x in 0..width {
+                let i = (y * width + x) as usize;
+                let c = self.psi[i];
+                let laplacian =
+                    at(x - 1, y, c) + at(x + 1, y, c) + at(x, y - 1, c) + at(x, y + 1, c) - c * 4.0;
+                h_psi[i] = -laplacian * coupling + c * self.template.cells[i][2] as f64;
+            }
+        }
+
+        h_psi
+    }
+
+    /// The largest step that damps every state, rather than letting the highest ones oscillate
+    /// and grow. By Gershgorin's theorem no energy on the grid exceeds `4ħ²/(m·dx²) + max(V)`.
+    fn time_step(&self, params: &StepParams) -> f64 {
+        let max_potential = self
+            .template
+            .cells
+            .iter()
+            .map(|cell| cell[2] as f64)
+            .fold(0.0, f64::max);
+        let coupling = (params.hbar * params.kinetic_coefficient()) as f64
+            / (params.cell_spacing as f64).powi(2);
+        1.0 / (8.0 * coupling + max_potential)
+    }
+
+    /// The smallest kinetic energy a wave spanning the whole grid can have, which stands in for
+    /// the energy of states close to 0 when judging convergence.
+    fn energy_scale(&self, params: &StepParams) -> f64 {
+        let length =
+            self.template.width.max(self.template.height) as f64 * params.cell_spacing as f64;
+        (params.hbar * params.kinetic_coefficient()) as f64
+            * (std::f64::consts::PI / length).powi(2)
+    }
+}
+
+/// Writes every state to `eigenstate_<i>.qstate` in `dir`, and their energies to
+/// `eigenstates.csv`.
+pub fn export(states: &[Eigenstate], params: &StepParams, dir: &Path) -> io::Result<()> {
+    fs::create_dir_all(dir)?;
+
+    let mut log = String::from("index,energy,residual,iterations\n");
+    for (i, state) in states.iter().enumerate() {
+        log += &format!(
+            "{},{},{},{}\n",
+            i, state.energy, state.residual, state.iterations
+        );
+        SimulationState {
+            grid: state.grid.clone(),
+            params: *params,
+            step_count: 0,
+            time: 0.0,
+        }
+        .save(&dir.join(format!("eigenstate_{:02}.qstate", i)))?;
+    }
+
+    fs::write(dir.join("eigenstates.csv"), log)
+}
+
+/// A reproducible value in `[-1, 1)` for cell `i`, using the SplitMix64 mixing function.
+fn noise(i: u64, seed: u64) -> f64 {
+    let z = random::mix(
+        i.wrapping_add(seed.wrapping_mul(0x632b_e59b_d9b4_e019))
+            .wrapping_mul(random::GOLDEN_GAMMA),
+    );
+    (z >> 11) as f64 / (1u64 << 52) as f64 - 1.0
+}
@@ -0,0 +1,768 @@
+use std::collections::HashMap;
+
+use crate::grid::Grid;
+
+/// The deepest stack that a compiled expression may need, which must match
+/// `EXPRESSION_STACK_SIZE` in `drives.glsl`.
+const STACK_SIZE: usize = 16;
+
+/// The variables an expression can refer to.
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Variable {
+    X = 1,
+    Y = 2,
+    /// The distance from the origin, `√(x² + y²)`.
+    R = 3,
+    T = 4,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Unary {
+    Negate = 10,
+    Not = 11,
+    Abs = 12,
+    Sqrt = 13,
+    Exp = 14,
+    Ln = 15,
+    Sin = 16,
+    Cos = 17,
+    Tan = 18,
+    Tanh = 19,
+    Floor = 20,
+    Ceil = 21,
+    Sign = 22,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Binary {
+    Add = 30,
+    Subtract = 31,
+    Multiply = 32,
+    Divide = 33,
+    Power = 34,
+    Less = 35,
+    LessEqual = 36,
+    Greater = 37,
+    GreaterEqual = 38,
+    Equal = 39,
+    NotEqual = 40,
+    And = 41,
+    Or = 42,
+    Min = 43,
+    Max = 44,
+    Atan2 = 45,
+}
+
+impl Unary {
+    fn apply(self, a: f64) -> f64 {
+        match self {
+            Unary::Negate => -a,
+            Unary::Not => truth(a == 0.0),
+            Unary::Abs => a.abs(),
+            Unary::Sqrt => a.sqrt(),
+            Unary::Exp => a.exp(),
+            Unary::Ln => a.ln(),
+            Unary::Sin => a.sin(),
+            Unary::Cos => a.cos(),
+            Unary::Tan => a.tan(),
+            Unary::Tanh => a.tanh(),
+            Unary::Floor => a.floor(),
+            Unary::Ceil => a.ceil(),
+            // Unlike `f64::signum`, 0 has no sign, like GLSL's `sign`
+            Unary::Sign => {
+                if a == 0.0 {
+                    0.0
+                } else {
+                    a.signum()
+                }
+            }
+        }
+    }
+
+    fn function(name: &str) -> Option<Unary> {
+        Some(match name {
+            "abs" => Unary::Abs,
+            "sqrt" => Unary::Sqrt,
+            "exp" => Unary::Exp,
+            "ln" | "log" => Unary::Ln,
+            "sin" => Unary::Sin,
+            "cos" => Unary::Cos,
+            "tan" => Unary::Tan,
+            "tanh" => Unary::Tanh,
+            "floor" => Unary::Floor,
+            "ceil" => Unary::Ceil,
+            "sign" => Unary::Sign,
+            _ => return None,
+        })
+    }
+}
+
+impl Binary {
+    fn apply(self, a: f64, b: f64) -> f64 {
+        match self {
+            Binary::Add => a + b,
+            Binary::Subtract => a - b,
+            Binary::Multiply => a * b,
+            Binary::Divide => a / b,
+            Binary::Power => a.powf(b),
+            Binary::Less => truth(a < b),
+            Binary::LessEqual => truth(a <= b),
+            Binary::Greater => truth(a > b),
+            Binary::GreaterEqual => truth(a >= b),
+            Binary::Equal => truth(a == b),
+            Binary::NotEqual => truth(a != b),
+            Binary::And => truth(a != 0.0 && b != 0.0),
+            Binary::Or => truth(a != 0.0 || b != 0.0),
+            Binary::Min => a.min(b),
+            Binary::Max => a.max(b),
+            Binary::Atan2 => a.atan2(b),
+        }
+    }
+
+    fn function(name: &str) -> Option<Binary> {
+        Some(match name {
+            "min" => Binary::Min,
+            "max" => Binary::Max,
+            "atan2" => Binary::Atan2,
+            _ => return None,
+        })
+    }
+}
+
+/// Conditions are 1 when they hold and 0 otherwise, so that `V0*(abs(x)<w)` is a barrier.
+fn truth(condition: bool) -> f64 {
+    if condition { 1.0 } else { 0.0 }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Node {
+    Number(f64),
+    Variable(Variable),
+    Unary(Unary, Box<Node>),
+    Binary(Binary, Box<Node>, Box<Node>),
+}
+
+impl Node {
+    /// Applies `op` to `a`, folding it into a number straight away if `a` is one.
+    fn unary(op: Unary, a: Node) -> Node {
+        match a {
+            Node::Number(a) => Node::Number(op.apply(a)),
+            a => Node::Unary(op, Box::new(a)),
+        }
+    }
+
+    fn binary(op: Binary, a: Node, b: Node) -> Node {
+        match (a, b) {
+            (Node::Number(a), Node::Number(b)) => Node::Number(op.apply(a, b)),
+            (a, b) => Node::Binary(op, Box::new(a), Box::new(b)),
+        }
+    }
+
+    fn evaluate(&self, variables: &[f64; 4]) -> f64 {
+        match self {
+            Node::Number(value) => *value,
+            Node::Variable(variable) => variables[*variable as usize - 1],
+            Node::Unary(op, a) => op.apply(a.evaluate(variables)),
+            Node::Binary(op, a, b) => op.apply(a.evaluate(variables), b.evaluate(variables)),
+        }
+    }
+
+    fn uses(&self, variable: Variable) -> bool {
+        match self {
+            Node::Number(_) => false,
+            Node::Variable(v) => *v == variable,
+            Node::Unary(_, a) => a.uses(variable),
+            Node::Binary(_, a, b) => a.uses(variable) || b.uses(variable),
+        }
+    }
+
+    /// The number of values on the stack while evaluating the compiled node.
+    fn stack_depth(&self) -> usize {
+        match self {
+            Node::Number(_) | Node::Variable(_) => 1,
+            Node::Unary(_, a) => a.stack_depth(),
+            Node::Binary(_, a, b) => a.stack_depth().max(1 + b.stack_depth()),
+        }
+    }
+
+    /// Appends the instructions that leave the value of the node on top of the stack.
+    fn compile(&self, program: &mut Vec<[f32; 2]>) {
+        match self {
+            Node::Number(value) => program.push([0.0, *value as f32]),
+            Node::Variable(variable) => program.push([*variable as u32 as f32, 0.0]),
+            Node::Unary(op, a) => {
+                a.compile(program);
+                program.push([*op as u32 as f32, 0.0]);
+            }
+            Node::Binary(op, a, b) => {
+                a.compile(program);
+                b.compile(program);
+                program.push([*op as u32 as f32, 0.0]);
+            }
+        }
+    }
+}
+
+/// A formula in `x`, `y`, `r` and `t`, such as `0.5*k*(x^2+y^2)` or `V0*(abs(x)<w)`.
+///
+/// It may start with definitions separated by semicolons, like `k = 0.01; 0.5*k*r^2`, which later
+/// definitions and the formula can use. Besides `+ - * / ^`, there are the comparisons
+/// `< <= > >= == !=`, `&&`, `||` and `!`, which give 1 when they hold and 0 otherwise, the
+/// constants `pi` and `e`, and the functions `abs sqrt exp ln sin cos tan tanh floor ceil sign`
+/// and `min max atan2` of two arguments.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Expression {
+    /// The text it was parsed from.
+    pub source: String,
+    root: Node,
+}
+
+impl Expression {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let tokens = tokenize(text)?;
+        let mut parser = Parser {
+            tokens,
+            position: 0,
+            definitions: HashMap::new(),
+        };
+        let root = parser.program()?;
+
+        if root.stack_depth() > STACK_SIZE {
+            return Err(String::from("the expression is nested too deeply"));
+        }
+
+        Ok(Expression {
+            source: String::from(text.trim()),
+            root,
+        })
+    }
+
+    pub fn depends_on_time(&self) -> bool {
+        self.root.uses(Variable::T)
+    }
+
+    pub fn evaluate(&self, x: f64, y: f64, t: f64) -> f64 {
+        self.root.evaluate(&[x, y, x.hypot(y), t])
+    }
+
+    /// Compiles the expression into `[opcode, value]` instructions for the stack machine in
+    /// `drives.glsl`, which runs them in order. Numbers push their value, variables push theirs,
+    /// and operators and functions replace the values they take from the top of the stack with
+    /// their result.
+    pub fn compile(&self) -> Vec<[f32; 2]> {
+        let mut program = Vec::new();
+        self.root.compile(&mut program);
+        program
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(f64),
+    Name(String),
+    Symbol(&'static str),
+}
+
+/// The symbols, with the longer ones first so that `<=` is not read as `<` followed by `=`.
+const SYMBOLS: &[&str] = &[
+    "<=", ">=", "==", "!=", "&&", "||", "+", "-", "*", "/", "^", "(", ")", ",", "<", ">", "!", "=",
+    ";",
+];
+
+/// Splits `text` into tokens, each with the character position it starts at.
+fn tokenize(text: &str) -> Result<Vec<(Token, usize)>, String> {
+    let chars = text.chars().collect::<Vec<_>>();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        let start = i;
+        if c.is_whitespace() {
+            i += 1;
+        } else if c.is_ascii_digit() || c == '.' {
+            while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.') {
+                i += 1;
+            }
+            // An exponent, as in 1e-3
+            if i < chars.len() && (chars[i] == 'e' || chars[i] == 'E') {
+                let mut j = i + 1;
+                if j < chars.len() && (chars[j] == '+' || chars[j] == '-') {
+                    j += 1;
+                }
+                if j < chars.len() && chars[j].is_ascii_digit() {
+                    i = j;
+                    while i < chars.len() && chars[i].is_ascii_digit() {
+                        i += 1;
+                    }
+                }
+            }
+            let number = chars[start..i].iter().collect::<String>();
+            let value = number
+                .parse()
+                .map_err(|_| format!("invalid number {} at {}", number, start + 1))?;
+            tokens.push((Token::Number(value), start));
+        } else if c.is_alphabetic() || c == '_' {
+            while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            tokens.push((Token::Name(chars[start..i].iter().collect()), start));
+        } else {
+            let rest = chars[i..].iter().collect::<String>();
+            let symbol = SYMBOLS
+                .iter()
+                .find(|symbol| rest.starts_with(**symbol))
+                .ok_or_else(|| format!("unexpected {} at {}", c, start + 1))?;
+            i += symbol.chars().count();
+            tokens.push((Token::Symbol(symbol), start));
+        }
+    }
+
+    Ok(tokens)
+}
+
+/// A recursive descent parser, from the loosest binding operators to the tightest.
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    position: usize,
+    definitions: HashMap<String, Node>,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position).map(|(token, _)| token)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.peek().cloned();
+        self.position += 1;
+        token
+    }
+
+    /// Consumes the next token if it is `symbol`.
+    fn accept(&mut self, symbol: &'static str) -> bool {
+        if self.peek() == Some(&Token::Symbol(symbol)) {
+            self.position += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, symbol: &'static str) -> Result<(), String> {
+        if self.accept(symbol) {
+            Ok(())
+        } else {
+            Err(format!("expected {} {}", symbol, self.location()))
+        }
+    }
+
+    /// Where the next token is, for error messages.
+    fn location(&self) -> String {
+        match self.tokens.get(self.position) {
+            Some((_, start)) => format!("at {}", start + 1),
+            None => String::from("at the end"),
+        }
+    }
+
+    fn program(&mut self) -> Result<Node, String> {
+        loop {
+            // A definition is a name followed by a single `=`
+            let definition = match (self.peek(), self.tokens.get(self.position + 1)) {
+                (Some(Token::Name(name)), Some((Token::Symbol("="), _))) => Some(name.clone()),
+                _ => None,
+            };
+            let Some(name) = definition else {
+                break;
+            };
+            if is_reserved(&name) {
+                return Err(format!("{} cannot be redefined", name));
+            }
+
+            self.position += 2;
+            let value = self.or()?;
+            self.expect(";")?;
+            self.definitions.insert(name, value);
+        }
+
+        if self.peek().is_none() {
+            return Err(String::from("missing the expression"));
+        }
+        let root = self.or()?;
+        self.accept(";");
+        match self.peek() {
+            None => Ok(root),
+            Some(_) => Err(format!("unexpected input {}", self.location())),
+        }
+    }
+
+    fn or(&mut self) -> Result<Node, String> {
+        let mut node = self.and()?;
+        while self.accept("||") {
+            node = Node::binary(Binary::Or, node, self.and()?);
+        }
+        Ok(node)
+    }
+
+    fn and(&mut self) -> Result<Node, String> {
+        let mut node = self.comparison()?;
+        while self.accept("&&") {
+            node = Node::binary(Binary::And, node, self.comparison()?);
+        }
+        Ok(node)
+    }
+
+    fn comparison(&mut self) -> Result<Node, String> {
+        let mut node = self.sum()?;
+        loop {
+            let op = match self.peek() {
+                Some(Token::Symbol("<")) => Binary::Less,
+                Some(Token::Symbol("<=")) => Binary::LessEqual,
+                Some(Token::Symbol(">")) => Binary::Greater,
+                Some(Token::Symbol(">=")) => Binary::GreaterEqual,
+                Some(Token::Symbol("==")) => Binary::Equal,
+                Some(Token::Symbol("!=")) => Binary::NotEqual,
+                _ => return Ok(node),
+            };
+            self.position += 1;
+            node = Node::binary(op, node, self.sum()?);
+        }
+    }
+
+    fn sum(&mut self) -> Result<Node, String> {
+        let mut node = self.product()?;
+        loop {
+            let op = match self.peek() {
+                Some(Token::Symbol("+")) => Binary::Add,
+                Some(Token::Symbol("-")) => Binary::Subtract,
+                _ => return Ok(node),
+            };
+            self.position += 1;
+            node = Node::binary(op, node, self.product()?);
+        }
+    }
+
+    fn product(&mut self) -> Result<Node, String> {
+        let mut node = self.unary()?;
+        loop {
+            let op = match self.peek() {
+                Some(Token::Symbol("*")) => Binary::Multiply,
+                Some(Token::Symbol("/")) => Binary::Divide,
+                _ => return Ok(node),
+            };
+            self.position += 1;
+            node = Node::binary(op, node, self.unary()?);
+        }
+    }
+
+    /// Signs bind looser than powers, so `-x^2` is `-(x^2)`.
+    fn unary(&mut self) -> Result<Node, String> {
+        if self.accept("-") {
+            Ok(Node::unary(Unary::Negate, self.unary()?))
+        } else if self.accept("!") {
+            Ok(Node::unary(Unary::Not, self.unary()?))
+        } else if self.accept("+") {
+            self.unary()
+        } else {
+            self.power()
+        }
+    }
+
+    /// Powers are right associative, so `2^3^2` is `2^(3^2)`.
+    fn power(&mut self) -> Result<Node, String> {
+        let base = self.primary()?;
+        if self.accept("^") {
+            Ok(Node::binary(Binary::Power, base, self.unary()?))
+        } else {
+            Ok(base)
+        }
+    }
+
+    fn primary(&mut self) -> Result<Node, String> {
+        let location = self.location();
+        match self.next() {
+            Some(Token::Number(value)) => Ok(Node::Number(value)),
+            Some(Token::Symbol("(")) => {
+                let node = self.or()?;
+                self.expect(")")?;
+                Ok(node)
+            }
+            Some(Token::Name(name)) if self.accept("(") => self.call(&name, &location),
+            Some(Token::Name(name)) => match name.as_str() {
+                "x" => Ok(Node::Variable(Variable::X)),
+                "y" => Ok(Node::Variable(Variable::Y)),
+                "r" => Ok(Node::Variable(Variable::R)),
+                "t" => Ok(Node::Variable(Variable::T)),
+                "pi" => Ok(Node::Number(std::f64::consts::PI)),
+                "e" => Ok(Node::Number(std::f64::consts::E)),
+                _ => self.definitions.get(&name).cloned().ok_or_else(|| {
+                    format!(
+                        "unknown name {} {}, define it first like {} = 1;",
+                        name, location, name
+                    )
+                }),
+            },
+            Some(Token::Symbol(symbol)) => Err(format!("unexpected {} {}", symbol, location)),
+            None => Err(String::from("the expression ends too early")),
+        }
+    }
+
+    /// Parses the arguments of a function, after its opening parenthesis.
+    fn call(&mut self, name: &str, location: &str) -> Result<Node, String> {
+        let mut arguments = vec![self.or()?];
+        while self.accept(",") {
+            arguments.push(self.or()?);
+        }
+        self.expect(")")?;
+
+        let count = arguments.len();
+        let mut arguments = arguments.into_iter();
+        if let Some(op) = Unary::function(name) {
+            if count != 1 {
+                return Err(format!("{} takes 1 argument, not {}", name, count));
+            }
+            Ok(Node::unary(op, arguments.next().unwrap()))
+        } else if let Some(op) = Binary::function(name) {
+            if count != 2 {
+                return Err(format!("{} takes 2 arguments, not {}", name, count));
+            }
+            let a = arguments.next().unwrap();
+            Ok(Node::binary(op, a, arguments.next().unwrap()))
+        } else {
+            Err(format!("unknown function {} {}", name, location))
+        }
+    }
+}
+
+fn is_reserved(name: &str) -> bool {
+    matches!(name, "x" | "y" | "r" | "t" | "pi" | "e")
+        || Unary::function(name).is_some()
+        || Binary::function(name).is_some()
+}
+
+/// An expression for a potential over the grid. Its x and y are measured in physical units from
+/// the centre of the grid it was made for, with y pointing down like the rows do.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PotentialExpression {
+    pub expression: Expression,
+    /// The cell at the origin.
+    center: (f32, f32),
+    cell_spacing: f32,
+}
+
+impl PotentialExpression {
+    pub fn new(expression: Expression, width: u32, height: u32, cell_spacing: f32) -> Self {
+        PotentialExpression {
+            expression,
+            center: ((width as f32 - 1.0) / 2.0, (height as f32 - 1.0) / 2.0),
+            cell_spacing,
+        }
+    }
+
+    /// The potential at the cell `(x, y)` at time `t`.
+    pub fn value(&self, x: f32, y: f32, t: f64) -> f32 {
+        let x = ((x - self.center.0) * self.cell_spacing) as f64;
+        let y = ((y - self.center.1) * self.cell_spacing) as f64;
+        self.expression.evaluate(x, y, t) as f32
+    }
+
+    /// Evaluates the expression at `t = 0` onto the potential channel of every cell of `grid`,
+    /// adding it to the potential that is there if `add` is set and replacing it otherwise.
+    pub fn draw(&self, grid: &mut Grid, add: bool) {
+        for y in 0..grid.height {
+            for x in 0..grid.width {
+                let index = grid.index(x, y);
+                let value = self.value(x as f32, y as f32, 0.0);
+                let cell = &mut grid.cells[index];
+                cell[2] = if add { cell[2] + value } else { value };
+            }
+        }
+    }
+
+    /// Packs the expression for `expression_potential` in `drives.glsl`: the origin and cell
+    /// spacing, then one instruction per `vec4`.
+    pub fn pack(&self) -> Vec<[f32; 4]> {
+        let mut packed = vec![[self.center.0, self.center.1, self.cell_spacing, 0.0]];
+        packed.extend(
+            self.expression
+                .compile()
+                .iter()
+                .map(|[op, value]| [*op, *value, 0.0, 0.0]),
+        );
+        packed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn value(text: &str) -> f64 {
+        Expression::parse(text).unwrap().evaluate(3.0, 4.0, 0.5)
+    }
+
+    fn error(text: &str) -> String {
+        Expression::parse(text).unwrap_err()
+    }
+
+    /// Runs a compiled program the way `expression_potential` in `drives.glsl` does, and returns
+    /// its value along with the most values that were on the stack at once.
+    fn run(program: &[[f32; 2]], variables: [f64; 4]) -> (f64, usize) {
+        const UNARY: &[Unary] = &[
+            Unary::Negate,
+            Unary::Not,
+            Unary::Abs,
+            Unary::Sqrt,
+            Unary::Exp,
+            Unary::Ln,
+            Unary::Sin,
+            Unary::Cos,
+            Unary::Tan,
+            Unary::Tanh,
+            Unary::Floor,
+            Unary::Ceil,
+            Unary::Sign,
+        ];
+        const BINARY: &[Binary] = &[
+            Binary::Add,
+            Binary::Subtract,
+            Binary::Multiply,
+            Binary::Divide,
+            Binary::Power,
+            Binary::Less,
+            Binary::LessEqual,
+            Binary::Greater,
+            Binary::GreaterEqual,
+            Binary::Equal,
+            Binary::NotEqual,
+            Binary::And,
+            Binary::Or,
+            Binary::Min,
+            Binary::Max,
+            Binary::Atan2,
+        ];
+
+        let mut stack = Vec::new();
+        let mut depth = 0;
+        for &[op, value] in program {
+            let op = op as u32;
+            if op == 0 {
+                stack.push(value as f64);
+            } else if op <= 4 {
+                stack.push(variables[op as usize - 1]);
+            } else if let Some(unary) = UNARY.iter().find(|unary| **unary as u32 == op) {
+                let a = stack.pop().unwrap();
+                stack.push(unary.apply(a));
+            } else {
+                let binary = BINARY.iter().find(|binary| **binary as u32 == op).unwrap();
+                let b = stack.pop().unwrap();
+                let a = stack.pop().unwrap();
+                stack.push(binary.apply(a, b));
+            }
+            depth = depth.max(stack.len());
+        }
+        assert_eq!(stack.len(), 1);
+        (stack[0], depth)
+    }
+
+    #[test]
+    fn precedence() {
+        assert_eq!(value("1 + 2*3"), 7.0);
+        assert_eq!(value("(1 + 2)*3"), 9.0);
+        assert_eq!(value("8/4/2"), 1.0);
+        assert_eq!(value("10 - 4 - 3"), 3.0);
+        assert_eq!(value("1 + 1 < 3"), 1.0);
+        assert_eq!(value("1 || 0 && 0"), 1.0);
+        assert_eq!(value("!0 + 1"), 2.0);
+        assert_eq!(value("x*y + r"), 17.0);
+    }
+
+    #[test]
+    fn powers() {
+        assert_eq!(value("2^3^2"), 512.0);
+        assert_eq!(value("-x^2"), -9.0);
+        assert_eq!(value("-2^2"), -4.0);
+        assert_eq!(value("(-2)^3"), -8.0);
+        assert_eq!(value("2^-1"), 0.5);
+        assert_eq!(value("2*3^2"), 18.0);
+    }
+
+    #[test]
+    fn definitions() {
+        assert_eq!(value("k = 2; m = k*3; m + x"), 9.0);
+        assert_eq!(value("a = 1; a = a + 1; a;"), 2.0);
+        assert_eq!(error("x = 1; x"), "x cannot be redefined");
+        assert_eq!(error("pi = 3; pi"), "pi cannot be redefined");
+        assert_eq!(error("sin = 1; sin"), "sin cannot be redefined");
+        assert_eq!(
+            error("k*x"),
+            "unknown name k at 1, define it first like k = 1;"
+        );
+        assert_eq!(error("k = 1"), "expected ; at the end");
+        assert_eq!(error("k = 1;"), "missing the expression");
+    }
+
+    #[test]
+    fn error_positions() {
+        assert_eq!(error(""), "missing the expression");
+        assert_eq!(error("1 + * 2"), "unexpected * at 5");
+        assert_eq!(error("(1 + 2"), "expected ) at the end");
+        assert_eq!(error("1 $ 2"), "unexpected $ at 3");
+        assert_eq!(error("1 2"), "unexpected input at 3");
+        assert_eq!(error("1 +"), "the expression ends too early");
+        assert_eq!(error("x + foo(1)"), "unknown function foo at 5");
+        assert_eq!(error("min(1)"), "min takes 2 arguments, not 1");
+        assert_eq!(error("abs(1, 2)"), "abs takes 1 argument, not 2");
+        assert_eq!(error("1..2"), "invalid number 1..2 at 1");
+    }
+
+    #[test]
+    fn time_dependence() {
+        assert!(!Expression::parse("x + pi").unwrap().depends_on_time());
+        assert!(
+            Expression::parse("w = t; sin(w)")
+                .unwrap()
+                .depends_on_time()
+        );
+    }
+
+    #[test]
+    fn compiled_programs_match_evaluate() {
+        let expressions = [
+            "0.5*0.0005*(x^2 + y^2)",
+            "-4/sqrt(r^2 + 2^2)",
+            "10*(abs(x) < 2)*(abs(abs(y) - 12) > 3)",
+            "k = 0.0005; 0.5*k*((x - 10*sin(0.05*t))^2 + y^2)",
+            "max(x, y) - min(x, -y) + atan2(y, x)",
+            "x + (y + (r + (t + (x + y))))",
+            "!(x > 0) || sign(y) == -1 && floor(t) != ceil(t)",
+            "-x^2 + exp(-r)*tanh(t) - ln(r)",
+        ];
+        for text in expressions {
+            let expression = Expression::parse(text).unwrap();
+            let program = expression.compile();
+            for (x, y, t) in [(3.0, 4.0, 0.5), (-2.5, 1.0, 7.0), (0.25, -6.0, 0.0)] {
+                let (value, depth) = run(&program, [x, y, f64::hypot(x, y), t]);
+                let expected = expression.evaluate(x, y, t);
+                assert!(
+                    (value - expected).abs() <= 1e-5 * expected.abs().max(1.0),
+                    "{}: {} != {}",
+                    text,
+                    value,
+                    expected
+                );
+                assert_eq!(depth, expression.root.stack_depth(), "{}", text);
+            }
+        }
+    }
+
+    #[test]
+    fn stack_limit() {
+        let nested = |depth: usize| "x + (".repeat(depth) + "x" + &")".repeat(depth);
+        assert!(Expression::parse(&nested(STACK_SIZE - 2)).is_ok());
+        assert_eq!(
+            error(&nested(STACK_SIZE)),
+            "the expression is nested too deeply"
+        );
+    }
+}
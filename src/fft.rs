@@ -0,0 +1,202 @@
+use std::f64::consts::PI;
+
+use crate::complex::Complex;
+
+/// Transforms `data` in place with the discrete Fourier transform, or its inverse (including the
+/// `1/n` normalisation) if `inverse` is set. Any length works, but powers of two are fastest.
+pub fn fft(data: &mut [Complex], inverse: bool) {
+    let n = data.len();
+    if n <= 1 {
+        return;
+    }
+
+    if inverse {
+        // ifft(x) = conj(fft(conj(x))) / n
+        data.iter_mut().for_each(|z| *z = z.conj());
+        fft(data, false);
+        let scale = 1.0 / n as f64;
+        data.iter_mut().for_each(|z| *z = z.conj() * scale);
+    } else if n.is_power_of_two() {
+        radix_2(data);
+    } else {
+        bluestein(data);
+    }
+}
+
+/// Transforms a `width` by `height` array stored row by row, one row and one column at a time.
+pub fn fft_2d(data: &mut [Complex], width: usize, height: usize, inverse: bool) {
+    for row in data.chunks_exact_mut(width) {
+        fft(row, inverse);
+    }
+
+    let mut column = vec![Complex::ZERO; height];
+    for x in 0..width {
+        for (y, value) in column.iter_mut().enumerate() {
+            *value = data[y * width + x];
+        }
+        fft(&mut column, inverse);
+        for (y, value) in column.iter().enumerate() {
+            data[y * width + x] = *value;
+        }
+    }
+}
+
+/// The angular wave number of bin `i` of an `n` point transform over cells `spacing` apart, with
+/// the upper half of the bins standing for the negative frequencies.
+pub fn wave_number(i: usize, n: usize, spacing: f64) -> f64 {
+    let frequency = if i <= n / 2 {
+        i as f64
+    } else {
+        i as f64 - n as f64
+    };
+    2.0 * PI * frequency / (n as f64 * spacing)
+}
+
+/// The iterative Cooley–Tukey transform, for lengths that are powers of two.
+fn radix_2(data: &mut [Complex]) {
+    let n = data.len();
+
+    // Bit reversal permutation
+    let mut j = 0;
+    for i in 1..n {
+        let mut bit = n >> 1;
+        while j & bit != 0 {
+            j ^= bit;
+            bit >>= 1;
+        }
+        j |= bit;
+        if i < j {
+            data.swap(i, j);
+        }
+    }
+
+    let mut length = 2;
+    while length <= n {
+        let step = Complex::from_phase(-2.0 * PI / length as f64);
+        for start in (0..n).step_by(length) {
+            let mut twiddle = Complex::ONE;
+            for k in 0..length / 2 {
+                let even = data[start + k];
+                let odd = data[start + k + length / 2] * twiddle;
+                data[start + k] = even + odd;
+                data[start + k + length / 2] = even - odd;
+                twiddle = twiddle * step;
+            }
+        }
+        length <<= 1;
+    }
+}
+
+/// Bluestein's algorithm, which rewrites a transform of any length as a convolution that can be
+/// done with power of two transforms.
+fn bluestein(data: &mut [Complex]) {
+    let n = data.len();
+    let m = (2 * n - 1).next_power_of_two();
+
+    // The chirp e^(-iπk²/n), with k² taken modulo 2n to keep the phase accurate
+    let chirp = (0..n)
+        .map(|k| Complex::from_phase(-PI * ((k * k) % (2 * n)) as f64 / n as f64))
+        .collect::<Vec<_>>();
+
+    let mut a = vec![Complex::ZERO; m];
+    for k in 0..n {
+        a[k] = data[k] * chirp[k];
+    }
+
+    let mut b = vec![Complex::ZERO; m];
+    b[0] = chirp[0].conj();
+    for k in 1..n {
+        b[k] = chirp[k].conj();
+        b[m - k] = chirp[k].conj();
+    }
+
+    radix_2(&mut a);
+    radix_2(&mut b);
+    for (a, b) in a.iter_mut().zip(&b) {
+        *a = *a * *b;
+    }
+    fft(&mut a, true);
+
+    for k in 0..n {
+        data[k] = a[k] * chirp[k];
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Deterministic values that are neither real nor symmetric
+    fn signal(n: usize) -> Vec<Complex> {
+        (0..n)
+            .map(|i| {
+                let i = i as f64;
+                Complex::new((0.7 * i).sin() + 0.1 * i, (1.3 * i).cos() - 0.2)
+            })
+            .collect()
+    }
+
+    /// The transform straight from its definition, in O(n²)
+    fn naive_dft(data: &[Complex]) -> Vec<Complex> {
+        let n = data.len();
+        (0..n)
+            .map(|k| {
+                let mut sum = Complex::ZERO;
+                for (j, value) in data.iter().enumerate() {
+                    sum +=
+                        *value * Complex::from_phase(-2.0 * PI * ((j * k) % n) as f64 / n as f64);
+                }
+                sum
+            })
+            .collect()
+    }
+
+    fn max_error(a: &[Complex], b: &[Complex]) -> f64 {
+        a.iter()
+            .zip(b)
+            .map(|(a, b)| (*a - *b).norm_sqr().sqrt())
+            .fold(0.0, f64::max)
+    }
+
+    #[test]
+    fn matches_naive_dft() {
+        // 64 goes through radix_2, 45 and 7 through bluestein
+        for n in [64, 45, 7] {
+            let input = signal(n);
+            let mut data = input.clone();
+            fft(&mut data, false);
+            assert!(max_error(&data, &naive_dft(&input)) < 1e-9, "length {}", n);
+        }
+    }
+
+    #[test]
+    fn inverse_undoes_forward() {
+        for n in [64, 45, 7] {
+            let input = signal(n);
+            let mut data = input.clone();
+            fft(&mut data, false);
+            fft(&mut data, true);
+            assert!(max_error(&data, &input) < 1e-12, "length {}", n);
+        }
+    }
+
+    #[test]
+    fn inverse_undoes_forward_in_2d() {
+        let (width, height) = (8, 6);
+        let input = signal(width * height);
+        let mut data = input.clone();
+        fft_2d(&mut data, width, height, false);
+        fft_2d(&mut data, width, height, true);
+        assert!(max_error(&data, &input) < 1e-12);
+    }
+
+    #[test]
+    fn wave_numbers_wrap_to_negative_frequencies() {
+        let dk = 2.0 * PI / 8.0;
+        assert_eq!(wave_number(0, 8, 1.0), 0.0);
+        assert!((wave_number(3, 8, 1.0) - 3.0 * dk).abs() < 1e-12);
+        assert!((wave_number(4, 8, 1.0) - 4.0 * dk).abs() < 1e-12);
+        assert!((wave_number(5, 8, 1.0) + 3.0 * dk).abs() < 1e-12);
+        assert!((wave_number(5, 8, 0.5) + 6.0 * dk).abs() < 1e-12);
+    }
+}
@@ -0,0 +1,117 @@
+/// A host-side copy of the simulation grid.
+///
+/// Every cell uses the same layout as the `R32G32B32A32_SFLOAT` image in the compute shader:
+/// `[Re(Ψ), Im(Ψ), potential, previous Im(Ψ)]`. Cells are stored row by row, starting at the
+/// top left of the domain.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Grid {
+    pub width: u32,
+    pub height: u32,
+    pub cells: Vec<[f32; 4]>,
+}
+
+impl Grid {
+    pub fn new(width: u32, height: u32) -> Self {
+        Grid {
+            width,
+            height,
+            cells: vec![[0.0; 4]; (width * height) as usize],
+        }
+    }
+
+    pub fn index(&self, x: u32, y: u32) -> usize {
+        (y * self.width + x) as usize
+    }
+
+    pub fn get(&self, x: u32, y: u32) -> [f32; 4] {
+        self.cells[self.index(x, y)]
+    }
+
+    pub fn set(&mut self, x: u32, y: u32, cell: [f32; 4]) {
+        let index = self.index(x, y);
+        self.cells[index] = cell;
+    }
+
+    /// Bilinearly resamples every channel onto a grid of a different size, so that the grid
+    /// covers the same area with more or fewer cells.
+    pub fn resample(&self, width: u32, height: u32) -> Grid {
+        let mut grid = Grid::new(width, height);
+        if self.cells.is_empty() {
+            return grid;
+        }
+
+        // Maps the centre of a new cell to a position between the centres of the old cells
+        let source = |i: u32, new_len: u32, old_len: u32| -> (u32, u32, f32) {
+            let position = ((i as f32 + 0.5) * old_len as f32 / new_len as f32 - 0.5)
+                .clamp(0.0, (old_len - 1) as f32);
+            let i0 = position.floor() as u32;
+            (i0, (i0 + 1).min(old_len - 1), position - i0 as f32)
+        };
+
+        for y in 0..height {
+            let (y0, y1, fy) = source(y, height, self.height);
+            for x in 0..width {
+                let (x0, x1, fx) = source(x, width, self.width);
+                let (a, b) = (self.get(x0, y0), self.get(x1, y0));
+                let (c, d) = (self.get(x0, y1), self.get(x1, y1));
+
+                let cell = std::array::from_fn(|i| {
+                    let top = a[i] + (b[i] - a[i]) * fx;
+                    let bottom = c[i] + (d[i] - c[i]) * fx;
+                    top + (bottom - top) * fy
+                });
+                grid.set(x, y, cell);
+            }
+        }
+
+        grid
+    }
+
+    /// |Ψ|² of the cell at `index`, using the staggered leapfrog values the same way `frag.glsl`
+    /// does.
+    pub fn probability(&self, index: usize) -> f32 {
+        let [u, v, _, old_v] = self.cells[index];
+        u * u + v * old_v
+    }
+
+    /// The largest absolute difference between any channel of any two corresponding cells.
+    ///
+    /// Used to check one solver's output against another's. A NaN anywhere makes the result NaN,
+    /// and grids of different sizes are infinitely far apart.
+    pub fn max_difference(&self, other: &Grid) -> f32 {
+        if self.width != other.width || self.height != other.height {
+            return f32::INFINITY;
+        }
+
+        self.cells
+            .iter()
+            .zip(other.cells.iter())
+            .flat_map(|(a, b)| a.iter().zip(b.iter()).map(|(a, b)| (a - b).abs()))
+            .fold(0.0, |max, d| if d > max || d.is_nan() { d } else { max })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn max_difference() {
+        let mut a = Grid::new(2, 2);
+        a.set(1, 0, [1.0, -2.0, 3.0, 0.5]);
+        let mut b = a.clone();
+        assert_eq!(a.max_difference(&b), 0.0);
+
+        b.set(1, 0, [1.0, -2.5, 3.25, 0.5]);
+        b.set(0, 1, [0.0, 0.0, 0.0, -0.125]);
+        assert_eq!(a.max_difference(&b), 0.5);
+        assert_eq!(b.max_difference(&a), 0.5);
+
+        // A NaN is never hidden by a larger difference elsewhere
+        b.set(0, 0, [f32::NAN, 0.0, 0.0, 0.0]);
+        assert!(a.max_difference(&b).is_nan());
+
+        assert_eq!(a.max_difference(&Grid::new(2, 3)), f32::INFINITY);
+        assert_eq!(a.max_difference(&Grid::new(4, 1)), f32::INFINITY);
+    }
+}
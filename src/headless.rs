@@ -0,0 +1,440 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::app::VulkanManager;
+use crate::drive::{self, DrivenPotential, DrivenSolver};
+use crate::eigenstates::{self, EigenstateSearch};
+use crate::grid::Grid;
+use crate::observables::Observables;
+use crate::scene::Scene;
+use crate::simulator::{GpuSolver, Simulator};
+use crate::solver::{self, Solver, StepParams};
+use crate::state_file::SimulationState;
+use crate::ui_state::{BoundaryCondition, Integrator, Units};
+use crate::wave_packet::WavePacket;
+
+pub const USAGE: &str = "\
+Usage: quantum-echoes run --steps <N> --out <DIR> [OPTIONS]
+
+Runs a simulation without opening a window.
+
+Options:
+    --steps <N>                 Number of time steps to advance
+    --out <DIR>                 Directory to write the results to
+    --state <FILE>              Start from a saved state instead of an empty grid, continuing
+                                its simulation clock. Its size and physics parameters apply
+                                unless given explicitly, in which case the state is
+                                resampled to the given size
+    --scene <FILE>              Start from a scene file instead of an empty grid. Its size
+                                and physics parameters apply unless given explicitly
+    --size <WxH>                Grid size in cells [default: 256x256]
+    --time-step <DT>            Time step of each leapfrog step [default: 0.18]
+    --boundary <BC>             dirichlet, neumann, periodic or absorbing [default: neumann]
+    --absorbing-width <CELLS>   Width of the absorbing layer [default: 24]
+    --absorbing-strength <W>    Strength of the absorbing layer at the edges [default: 0.5]
+    --cell-spacing <DX>         Physical distance between neighbouring cells [default: 1]
+    --units <UNITS>             natural or nanometre (nm, fs, eV), which also sets ħ and the
+                                mass to their values for an electron [default: natural]
+    --hbar <HBAR>               Reduced Planck constant [default: 1]
+    --mass <M>                  Mass of the particle [default: 1]
+    --integrator <NAME>         leapfrog, crank-nicolson or split-step [default: leapfrog].
+                                split-step needs the periodic boundary condition
+    --packet <X,Y,S,A,T,V>      Add a Gaussian wave packet at cell (X, Y) with σ S cells,
+                                amplitude A, direction T (radians) and speed V in physical
+                                units. May be repeated
+    --every <K>                 Also write a snapshot every K steps
+    --eigenstates <N>           Find the lowest N eigenstates of the potential with imaginary
+                                time propagation instead, taking at most --steps steps for
+                                each. Always runs on the CPU
+    --cpu                       Use the CPU reference solver instead of the GPU
+";
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Backend {
+    Gpu,
+    Cpu,
+}
+
+#[derive(Debug, Clone)]
+pub struct HeadlessOptions {
+    pub steps: u32,
+    pub out: PathBuf,
+    pub state: Option<PathBuf>,
+    pub scene: Option<PathBuf>,
+    pub size: Option<(u32, u32)>,
+    pub time_step: Option<f32>,
+    pub boundary_condition: Option<BoundaryCondition>,
+    pub absorbing_width: Option<f32>,
+    pub absorbing_strength: Option<f32>,
+    pub cell_spacing: Option<f32>,
+    pub integrator: Option<Integrator>,
+    pub units: Option<Units>,
+    pub hbar: Option<f32>,
+    pub mass: Option<f32>,
+    pub packets: Vec<WavePacket>,
+    pub snapshot_every: Option<u32>,
+    pub eigenstates: Option<usize>,
+    pub backend: Backend,
+}
+
+impl HeadlessOptions {
+    /// Parses the arguments following the `run` subcommand.
+    pub fn parse(args: &[String]) -> Result<Self, String> {
+        let mut steps = None;
+        let mut out = None;
+        let mut options = HeadlessOptions {
+            steps: 0,
+            out: PathBuf::new(),
+            state: None,
+            scene: None,
+            size: None,
+            time_step: None,
+            boundary_condition: None,
+            absorbing_width: None,
+            absorbing_strength: None,
+            cell_spacing: None,
+            integrator: None,
+            units: None,
+            hbar: None,
+            mass: None,
+            packets: Vec::new(),
+            snapshot_every: None,
+            eigenstates: None,
+            backend: Backend::Gpu,
+        };
+
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let mut value = || {
+                args.next()
+                    .ok_or_else(|| format!("missing value for {}", arg))
+            };
+
+            match arg.as_str() {
+                "--steps" => steps = Some(parse_number(arg, value()?)?),
+                "--out" => out = Some(PathBuf::from(value()?)),
+                "--state" => options.state = Some(PathBuf::from(value()?)),
+                "--scene" => options.scene = Some(PathBuf::from(value()?)),
+                "--size" => {
+                    let size = value()?;
+                    let (width, height) = size
+                        .split_once('x')
+                        .ok_or_else(|| format!("expected WxH for --size, got {}", size))?;
+                    let size = (parse_number(arg, width)?, parse_number(arg, height)?);
+                    if size.0 == 0 || size.1 == 0 {
+                        return Err(String::from("the grid size must be at least 1x1"));
+                    }
+                    options.size = Some(size);
+                }
+                "--time-step" => options.time_step = Some(parse_number(arg, value()?)?),
+                "--boundary" => options.boundary_condition = Some(value()?.parse()?),
+                "--absorbing-width" => options.absorbing_width = Some(parse_number(arg, value()?)?),
+                "--absorbing-strength" => {
+                    options.absorbing_strength = Some(parse_number(arg, value()?)?)
+                }
+                "--cell-spacing" => options.cell_spacing = Some(parse_number(arg, value()?)?),
+                "--integrator" => options.integrator = Some(value()?.parse()?),
+                "--units" => options.units = Some(value()?.parse()?),
+                "--hbar" => options.hbar = Some(parse_number(arg, value()?)?),
+                "--mass" => options.mass = Some(parse_number(arg, value()?)?),
+                "--packet" => {
+                    let fields = value()?
+                        .split(',')
+                        .map(|field| parse_number(arg, field))
+                        .collect::<Result<Vec<f32>, _>>()?;
+                    let [x, y, sigma, amplitude, theta, speed] = fields[..] else {
+                        return Err(String::from("--packet expects 6 comma separated values"));
+                    };
+                    options.packets.push(WavePacket {
+                        x,
+                        y,
+                        sigma,
+                        amplitude,
+                        theta,
+                        speed,
+                    });
+                }
+                "--every" => options.snapshot_every = Some(parse_number(arg, value()?)?),
+                "--eigenstates" => options.eigenstates = Some(parse_number(arg, value()?)?),
+                "--cpu" => options.backend = Backend::Cpu,
+                other => return Err(format!("unknown argument {}", other)),
+            }
+        }
+
+        options.steps = steps.ok_or("--steps is required")?;
+        options.out = out.ok_or("--out is required")?;
+
+        if options.state.is_some() && options.scene.is_some() {
+            return Err(String::from("--state and --scene cannot be used together"));
+        }
+        if options.snapshot_every == Some(0) {
+            return Err(String::from("--every must be at least 1"));
+        }
+        if options.eigenstates == Some(0) {
+            return Err(String::from("--eigenstates must be at least 1"));
+        }
+        if options.hbar.is_some_and(|hbar| hbar <= 0.0) {
+            return Err(String::from("--hbar must be positive"));
+        }
+        if options.mass.is_some_and(|mass| mass <= 0.0) {
+            return Err(String::from("--mass must be positive"));
+        }
+
+        Ok(options)
+    }
+}
+
+fn parse_number<T: std::str::FromStr>(arg: &str, value: &str) -> Result<T, String> {
+    value
+        .trim()
+        .parse()
+        .map_err(|_| format!("invalid value {} for {}", value, arg))
+}
+
+/// Builds the initial grid, advances it and writes the results to `options.out`.
+pub fn run(options: &HeadlessOptions) -> io::Result<()> {
+    let mut state = SimulationState {
+        grid: Grid::new(256, 256),
+        params: StepParams::default(),
+        step_count: 0,
+        time: 0.0,
+    };
+
+    if let Some(path) = &options.state {
+        state = SimulationState::load(path)?;
+    }
+    if let Some((width, height)) = options.size
+        && (width, height) != (state.grid.width, state.grid.height)
+    {
+        state.grid = state.grid.resample(width, height);
+    }
+    let scene = options
+        .scene
+        .as_deref()
+        .map(Scene::load)
+        .transpose()
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+    if let Some(scene) = &scene {
+        let (width, height) = options.size.or(scene.size).unwrap_or((256, 256));
+        state.grid = Grid::new(width, height);
+        state.params = scene.step_params(state.params);
+    }
+    if let Some(time_step) = options.time_step {
+        state.params.time_step = time_step;
+    }
+    if let Some(boundary_condition) = options.boundary_condition {
+        state.params.boundary_condition = boundary_condition;
+    }
+    if let Some(absorbing_width) = options.absorbing_width {
+        state.params.absorbing_width = absorbing_width;
+    }
+    if let Some(absorbing_strength) = options.absorbing_strength {
+        state.params.absorbing_strength = absorbing_strength;
+    }
+    if let Some(cell_spacing) = options.cell_spacing {
+        state.params.cell_spacing = cell_spacing;
+    }
+    if let Some(integrator) = options.integrator {
+        state.params.integrator = integrator;
+    }
+    if let Some(units) = options.units {
+        state.params.units = units;
+        state.params.hbar = units.hbar();
+        state.params.mass = units.electron_mass();
+    }
+    if let Some(hbar) = options.hbar {
+        state.params.hbar = hbar;
+    }
+    if let Some(mass) = options.mass {
+        state.params.mass = mass;
+    }
+
+    // The packets are drawn last, since their momentum depends on the mass and ħ
+    if let Some(scene) = &scene {
+        scene.apply(&mut state.grid, &state.params);
+    }
+    for packet in &options.packets {
+        packet.apply(&mut state.grid, &state.params);
+    }
+
+    if !state
+        .params
+        .integrator
+        .supports(state.params.boundary_condition)
+    {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "the {} integrator cannot be used with the {:?} boundary condition",
+                state.params.integrator, state.params.boundary_condition
+            ),
+        ));
+    }
+
+    let drives = scene
+        .as_ref()
+        .map(|scene| scene.drives.clone())
+        .unwrap_or_default();
+    let max_potential = state
+        .grid
+        .cells
+        .iter()
+        .map(|cell| cell[2].abs())
+        .fold(0.0, f32::max)
+        + drives
+            .iter()
+            .map(|drive| drive.max_value(state.grid.width, state.grid.height))
+            .sum::<f32>();
+    let stability_limit = state.params.stability_limit(max_potential);
+    if state.params.integrator == Integrator::Leapfrog
+        && state.params.time_step > stability_limit
+        && options.eigenstates.is_none()
+    {
+        eprintln!(
+            "warning: the time step {} is above the stability limit of the leapfrog integrator, \
+             {}, so the simulation will diverge",
+            state.params.time_step, stability_limit
+        );
+    }
+
+    fs::create_dir_all(&options.out)?;
+
+    if let Some(count) = options.eigenstates {
+        return find_eigenstates(state, count, options);
+    }
+
+    match options.backend {
+        Backend::Cpu => {
+            let solver = solver::cpu_solver(state.params.integrator, state.grid);
+            simulate(
+                DrivenSolver::wrap(solver, &drives, state.time).as_mut(),
+                &state.params,
+                &drives,
+                (state.step_count, state.time),
+                options,
+            )
+        }
+        Backend::Gpu => {
+            let mgr = VulkanManager::headless();
+            let mut simulator = Simulator::new(&mgr, state.grid.width, state.grid.height);
+            simulator.set_drives(&mgr, drives.clone());
+            simulator.write_grid(&mgr, &state.grid);
+            simulator.set_clock(state.step_count, state.time);
+            simulate(
+                &mut GpuSolver::new(&mgr, &mut simulator),
+                &state.params,
+                &drives,
+                (state.step_count, state.time),
+                options,
+            )
+        }
+    }
+}
+
+/// Advances `solver`, which already applies the `drives`, logging the observables with the
+/// drives included in the potential energy. The steps and times are counted on from `clock`,
+/// the step count and time that the solver's grid was at to begin with.
+fn simulate(
+    solver: &mut dyn Solver,
+    params: &StepParams,
+    drives: &[DrivenPotential],
+    clock: (u64, f64),
+    options: &HeadlessOptions,
+) -> io::Result<()> {
+    let mut log = String::from(
+        "step,time,total_probability,mean_x,mean_y,momentum_x,momentum_y,\
+         kinetic_energy,potential_energy,total_energy\n",
+    );
+    let (start_step, start_time) = clock;
+    let mut snapshot = |step: u32, grid: &Grid| -> io::Result<()> {
+        let time = start_time + step as f64 * params.time_step as f64;
+        let step = start_step + step as u64;
+        let mut driven = grid.clone();
+        drive::add_potentials(&mut driven, drives, time);
+        let observables = Observables::from_grid(&driven, time, params);
+        log += &format!(
+            "{},{},{},{},{},{},{},{},{},{}\n",
+            step,
+            time,
+            observables.total_probability,
+            observables.mean_x,
+            observables.mean_y,
+            observables.momentum_x,
+            observables.momentum_y,
+            observables.kinetic_energy,
+            observables.potential_energy,
+            observables.total_energy()
+        );
+        write_probability_image(
+            &options.out.join(format!("probability_{:06}.pgm", step)),
+            grid,
+        )
+    };
+
+    snapshot(0, &solver.read_grid())?;
+    for step in 1..=options.steps {
+        solver.step(params);
+
+        let snapshot_due = options
+            .snapshot_every
+            .is_some_and(|every| step % every == 0);
+        if snapshot_due || step == options.steps {
+            snapshot(step, &solver.read_grid())?;
+        }
+    }
+
+    fs::write(options.out.join("probability.csv"), log)?;
+
+    SimulationState {
+        grid: solver.read_grid(),
+        params: *params,
+        step_count: start_step + options.steps as u64,
+        time: start_time + options.steps as f64 * params.time_step as f64,
+    }
+    .save(&options.out.join("final.qstate"))
+}
+
+/// Finds the lowest `count` eigenstates and writes each of them, along with an image of its
+/// probability density.
+fn find_eigenstates(
+    state: SimulationState,
+    count: usize,
+    options: &HeadlessOptions,
+) -> io::Result<()> {
+    let mut search = EigenstateSearch::new(state.grid, count);
+    while !search.is_finished() {
+        // States that have not converged within the step budget are kept as they are
+        if !search.iterate(&state.params, options.steps) {
+            search.accept();
+        }
+    }
+
+    for (i, eigenstate) in search.states.iter().enumerate() {
+        write_probability_image(
+            &options.out.join(format!("eigenstate_{:02}.pgm", i)),
+            &eigenstate.grid,
+        )?;
+    }
+    eigenstates::export(&search.states, &state.params, &options.out)
+}
+
+/// Writes |Ψ|² as a binary greyscale PGM image, normalised to the largest value in the grid.
+fn write_probability_image(path: &Path, grid: &Grid) -> io::Result<()> {
+    let probability = (0..grid.cells.len())
+        .map(|i| grid.probability(i))
+        .collect::<Vec<_>>();
+    let max = probability
+        .iter()
+        .copied()
+        .fold(f32::MIN_POSITIVE, f32::max);
+
+    let mut data = format!("P5\n{} {}\n255\n", grid.width, grid.height).into_bytes();
+    data.extend(
+        probability
+            .iter()
+            .map(|p| ((p / max).clamp(0.0, 1.0) * 255.0).round() as u8),
+    );
+
+    fs::write(path, data)
+}
@@ -0,0 +1,118 @@
+use std::f32::consts::TAU;
+use std::fs::File;
+use std::io::{self, BufReader, Error, ErrorKind};
+use std::path::Path;
+
+use crate::grid::Grid;
+
+/// A PNG image decoded to RGB colours between 0 and 1, stored row by row from the top left like
+/// the cells of a `Grid`.
+#[derive(Debug, Clone)]
+pub struct Image {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<[f32; 3]>,
+}
+
+impl Image {
+    /// Reads a PNG file of any colour type and bit depth. Greyscale images are expanded to RGB,
+    /// and transparent pixels are blended onto black.
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let mut decoder = png::Decoder::new(BufReader::new(File::open(path)?));
+        decoder.set_transformations(png::Transformations::normalize_to_color8());
+        let mut reader = decoder.read_info().map_err(invalid_data)?;
+        let size = reader
+            .output_buffer_size()
+            .ok_or_else(|| invalid_data("the image is too large"))?;
+        let mut buffer = vec![0; size];
+        let info = reader.next_frame(&mut buffer).map_err(invalid_data)?;
+
+        let channels = info.color_type.samples();
+        let pixels = buffer[..info.buffer_size()]
+            .chunks_exact(channels)
+            .map(|pixel| {
+                let (rgb, alpha) = match *pixel {
+                    [l] => ([l; 3], 255),
+                    [l, a] => ([l; 3], a),
+                    [r, g, b] => ([r, g, b], 255),
+                    [r, g, b, a] => ([r, g, b], a),
+                    _ => unreachable!("PNG pixels have 1 to 4 samples"),
+                };
+                rgb.map(|c| c as f32 * alpha as f32 / (255.0 * 255.0))
+            })
+            .collect();
+
+        Ok(Image {
+            width: info.width,
+            height: info.height,
+            pixels,
+        })
+    }
+
+    /// Turns every pixel into a cell with `cell` and bilinearly resamples the result so that it
+    /// covers a grid of `width` by `height` cells.
+    fn resample(&self, width: u32, height: u32, cell: impl Fn([f32; 3]) -> [f32; 4]) -> Grid {
+        let image = Grid {
+            width: self.width,
+            height: self.height,
+            cells: self.pixels.iter().map(|&rgb| cell(rgb)).collect(),
+        };
+        image.resample(width, height)
+    }
+
+    /// Replaces the potential in `grid` with the brightness of the image, stretched over the
+    /// whole grid, so that white is `scale` and black is 0. With `invert` black is `scale` and
+    /// white is 0 instead, for drawings of dark walls on a light background.
+    pub fn apply_potential(&self, grid: &mut Grid, scale: f32, invert: bool) {
+        let image = self.resample(grid.width, grid.height, |[r, g, b]| {
+            let brightness = 0.299 * r + 0.587 * g + 0.114 * b;
+            [scale * if invert { 1.0 - brightness } else { brightness }; 4]
+        });
+        for (cell, pixel) in grid.cells.iter_mut().zip(image.cells) {
+            cell[2] = pixel[2];
+        }
+    }
+
+    /// Replaces the wave function in `grid` with the image, stretched over the whole grid.
+    ///
+    /// The colours are read back the way the Wave Function layer in `frag.glsl` draws them: the
+    /// value (brightness) is |Ψ|², so white has |Ψ| = `amplitude`, and the hue is the phase, with
+    /// the full colour wheel from red through green and blue being one turn. The wave function is
+    /// interpolated rather than the colours, so that the phase winds smoothly between pixels. The
+    /// previous Im(Ψ) is set to the new Im(Ψ) like `WavePacket::apply` does.
+    pub fn apply_wave_function(&self, grid: &mut Grid, amplitude: f32) {
+        let image = self.resample(grid.width, grid.height, |rgb| {
+            let (hue, value) = hue_and_value(rgb);
+            let magnitude = amplitude * value.sqrt();
+            let phase = TAU * hue;
+            let (u, v) = (magnitude * phase.cos(), magnitude * phase.sin());
+            [u, v, 0.0, v]
+        });
+        for (cell, [u, v, _, _]) in grid.cells.iter_mut().zip(image.cells) {
+            *cell = [u, v, cell[2], v];
+        }
+    }
+}
+
+/// The hue, as a fraction of the colour wheel starting at red, and the value of an RGB colour,
+/// like `rgb2hsv` in `frag.glsl`.
+fn hue_and_value([r, g, b]: [f32; 3]) -> (f32, f32) {
+    let max = r.max(g).max(b);
+    let range = max - r.min(g).min(b);
+    if range <= 0.0 {
+        return (0.0, max);
+    }
+
+    let sector = if max == r {
+        (g - b) / range
+    } else if max == g {
+        2.0 + (b - r) / range
+    } else {
+        4.0 + (r - g) / range
+    };
+    ((sector / 6.0).rem_euclid(1.0), max)
+}
+
+fn invalid_data(err: impl ToString) -> Error {
+    Error::new(ErrorKind::InvalidData, err.to_string())
+}
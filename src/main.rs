@@ -1,16 +1,50 @@
 use winit::event_loop::*;
 
-use std::sync::Arc;
-
 mod app;
+mod complex;
+mod cpu_solver;
+mod crank_nicolson;
+mod detector;
+mod drive;
+mod eigenstates;
+mod expression;
+mod fft;
+mod grid;
+mod headless;
+mod image_import;
+mod measurement;
+mod momentum;
+mod observables;
 mod quad_renderer;
+mod random;
+mod scene;
+mod screen;
 mod simulator;
+mod solver;
+mod split_step;
+mod state_file;
 mod ui_state;
+mod wave_packet;
 
 use app::App;
+use headless::HeadlessOptions;
 
 fn main() {
+    let args = std::env::args().collect::<Vec<_>>();
+
+    if args.get(1).is_some_and(|arg| arg == "run") {
+        let options = HeadlessOptions::parse(&args[2..]).unwrap_or_else(|err| {
+            eprintln!("error: {}\n\n{}", err, headless::USAGE);
+            std::process::exit(2);
+        });
+        if let Err(err) = headless::run(&options) {
+            eprintln!("error: {}", err);
+            std::process::exit(1);
+        }
+        return;
+    }
+
     let event_loop = EventLoop::new().unwrap();
     let mut app = App::new(&event_loop);
-    let _ = event_loop.run_app(Arc::get_mut(&mut app).unwrap());
-}
\ No newline at end of file
+    let _ = event_loop.run_app(&mut app);
+}
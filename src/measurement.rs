@@ -0,0 +1,116 @@
+use crate::grid::Grid;
+use crate::random::Random;
+use crate::solver::StepParams;
+use crate::wave_packet::WavePacket;
+
+/// What a measurement of whether the particle is inside a region found.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct RegionOutcome {
+    /// Whether the particle was found inside.
+    pub inside: bool,
+    /// The probability of finding it inside, before the measurement.
+    pub probability: f64,
+}
+
+/// Measures the position of the particle: picks a cell at random with a probability proportional
+/// to |Ψ|² there, and collapses the wave function onto it by replacing it with a Gaussian wave
+/// packet at rest with a standard deviation of `sigma` cells, centred on the cell.
+///
+/// The total probability is the same afterwards, so that absorbed probability stays lost. Returns
+/// the cell that was found, or `None` if there is no probability anywhere, in which case the grid
+/// is left alone.
+pub fn measure_position(
+    grid: &mut Grid,
+    sigma: f32,
+    params: &StepParams,
+    random: &mut Random,
+) -> Option<(u32, u32)> {
+    let total = total_probability(grid);
+    if !total.is_finite() || total <= 0.0 {
+        return None;
+    }
+
+    let target = random.next_f64() * total;
+    let mut sum = 0.0;
+    let index = (0..grid.cells.len())
+        .find(|&i| {
+            sum += density(grid, i);
+            sum > target
+        })
+        .unwrap_or(grid.cells.len() - 1);
+    let (x, y) = (index as u32 % grid.width, index as u32 / grid.width);
+
+    for cell in &mut grid.cells {
+        *cell = [0.0, 0.0, cell[2], 0.0];
+    }
+    WavePacket {
+        x: x as f32,
+        y: y as f32,
+        sigma,
+        amplitude: 1.0,
+        theta: 0.0,
+        speed: 0.0,
+    }
+    .apply(grid, params);
+    renormalize(grid, total);
+    Some((x, y))
+}
+
+/// Measures whether the particle is in the cells for which `inside` holds, and collapses the wave
+/// function onto the answer: the wave function is projected onto the region if the particle was
+/// found there and onto the rest of the grid if not.
+///
+/// Like [`measure_position`] the total probability is the same afterwards, and `None` means there
+/// is no probability anywhere.
+pub fn measure_region(
+    grid: &mut Grid,
+    inside: impl Fn(u32, u32) -> bool,
+    random: &mut Random,
+) -> Option<RegionOutcome> {
+    let total = total_probability(grid);
+    if !total.is_finite() || total <= 0.0 {
+        return None;
+    }
+
+    let within = (0..grid.cells.len())
+        .filter(|&i| inside(i as u32 % grid.width, i as u32 / grid.width))
+        .map(|i| density(grid, i))
+        .sum::<f64>();
+    let probability = (within / total).clamp(0.0, 1.0);
+    let found = random.next_f64() < probability;
+
+    for i in 0..grid.cells.len() {
+        if inside(i as u32 % grid.width, i as u32 / grid.width) != found {
+            let [_, _, potential, _] = grid.cells[i];
+            grid.cells[i] = [0.0, 0.0, potential, 0.0];
+        }
+    }
+    renormalize(grid, total);
+    Some(RegionOutcome {
+        inside: found,
+        probability,
+    })
+}
+
+/// |Ψ|² at cell `i`, where the leapfrog value dipping just below 0 counts as 0.
+fn density(grid: &Grid, i: usize) -> f64 {
+    grid.probability(i).max(0.0) as f64
+}
+
+fn total_probability(grid: &Grid) -> f64 {
+    (0..grid.cells.len()).map(|i| density(grid, i)).sum()
+}
+
+/// Scales the wave function so that its total probability is `total` again.
+fn renormalize(grid: &mut Grid, total: f64) {
+    let current = total_probability(grid);
+    if current <= 0.0 {
+        return;
+    }
+    let scale = (total / current).sqrt() as f32;
+    for cell in &mut grid.cells {
+        cell[0] *= scale;
+        cell[1] *= scale;
+        cell[3] *= scale;
+    }
+}
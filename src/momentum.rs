@@ -0,0 +1,49 @@
+use crate::complex::Complex;
+use crate::fft;
+use crate::grid::Grid;
+
+/// The momentum-space wave function φ(k), the 2D Fourier transform of Ψ, on a grid of the same
+/// size.
+///
+/// The transform is shifted so that k = 0 is at the cell (`width / 2`, `height / 2`), with k
+/// growing to the right and down like the position does. The cells use the same layout as the
+/// position-space grid, `[Re(φ), Im(φ), 0, Im(φ)]`, so `Grid::probability` gives |φ(k)|². Only the
+/// shape of φ matters for the display, so it is scaled to make the largest |φ| 1.
+pub fn momentum_grid(grid: &Grid) -> Grid {
+    let (width, height) = (grid.width as usize, grid.height as usize);
+    let mut phi = grid
+        .cells
+        .iter()
+        .map(|&[u, v, _, _]| Complex::new(u as f64, v as f64))
+        .collect::<Vec<_>>();
+    fft::fft_2d(&mut phi, width, height, false);
+
+    let max = phi.iter().map(|z| z.norm_sqr()).fold(0.0, f64::max).sqrt();
+    let scale = if max > 0.0 { 1.0 / max } else { 0.0 };
+
+    let mut momentum = Grid::new(grid.width, grid.height);
+    for y in 0..height {
+        for x in 0..width {
+            let z = phi[shifted_bin(y, height) * width + shifted_bin(x, width)] * scale;
+            let (re, im) = (z.re as f32, z.im as f32);
+            momentum.cells[y * width + x] = [re, im, 0.0, im];
+        }
+    }
+    momentum
+}
+
+/// The wave numbers `(k_x, k_y)` at cell (`x`, `y`) of a [`momentum_grid`] for a `width` by
+/// `height` grid with cells `spacing` apart, in radians per unit length.
+pub fn wave_numbers(x: u32, y: u32, width: u32, height: u32, spacing: f32) -> (f64, f64) {
+    let (width, height) = (width as usize, height as usize);
+    let spacing = spacing as f64;
+    (
+        fft::wave_number(shifted_bin(x as usize, width), width, spacing),
+        fft::wave_number(shifted_bin(y as usize, height), height, spacing),
+    )
+}
+
+/// The bin of an `n` point transform shown at index `i` once k = 0 is moved to index `n / 2`.
+fn shifted_bin(i: usize, n: usize) -> usize {
+    (i + n - n / 2) % n
+}
@@ -0,0 +1,170 @@
+use std::fmt;
+
+use crate::grid::Grid;
+use crate::solver::StepParams;
+use crate::ui_state::BoundaryCondition;
+
+/// How much the total probability may grow over the probability it started with before the
+/// simulation counts as diverging. None of the integrators add probability, the absorbing layer
+/// only removes it.
+const PROBABILITY_GROWTH_LIMIT: f64 = 0.01;
+
+/// A sign that the simulation has become numerically unstable.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Instability {
+    /// The probability or the energy is NaN or infinite.
+    NotFinite,
+    /// The total probability grew by this fraction.
+    ProbabilityGrowth(f64),
+}
+
+impl fmt::Display for Instability {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Instability::NotFinite => write!(f, "the wave function became NaN or infinite"),
+            Instability::ProbabilityGrowth(growth) => {
+                write!(f, "the total probability grew by {:.1}%", growth * 100.0)
+            }
+        }
+    }
+}
+
+/// The total probability and the expectation values of the wave function at one point in time.
+///
+/// Positions are measured from the centre of the top left cell, with y pointing down the grid
+/// like the rows do. The expectation values are normalised by the total probability, so they do
+/// not depend on how much of the wave function has been absorbed.
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub struct Observables {
+    pub time: f64,
+    /// `Σ|Ψ|²` over the cells, with |Ψ|² from [`Grid::probability`].
+    pub total_probability: f64,
+    pub mean_x: f64,
+    pub mean_y: f64,
+    pub momentum_x: f64,
+    pub momentum_y: f64,
+    pub kinetic_energy: f64,
+    pub potential_energy: f64,
+}
+
+impl Observables {
+    pub fn total_energy(&self) -> f64 {
+        self.kinetic_energy + self.potential_energy
+    }
+
+    /// Checks the measurement for signs of instability, against the total probability measured
+    /// when the wave function was last known to be good.
+    pub fn instability(&self, baseline_probability: f64) -> Option<Instability> {
+        if !self.total_probability.is_finite() || !self.total_energy().is_finite() {
+            return Some(Instability::NotFinite);
+        }
+
+        let growth = self.total_probability / baseline_probability - 1.0;
+        (baseline_probability > 0.0 && growth > PROBABILITY_GROWTH_LIMIT)
+            .then_some(Instability::ProbabilityGrowth(growth))
+    }
+
+    /// Computes the observables from the sums over every cell of
+    /// `[|Ψ|², x·|Ψ|², y·|Ψ|², Im(Ψ*·∂Ψ/∂x), Im(Ψ*·∂Ψ/∂y), |∇Ψ|², V·|Ψ|², 0]`, with the
+    /// derivatives taken in cells rather than physical units, as `observables.glsl` does.
+    pub fn from_sums(sums: [f64; 8], time: f64, params: &StepParams) -> Self {
+        let [
+            probability,
+            x,
+            y,
+            current_x,
+            current_y,
+            gradient,
+            potential,
+            _,
+        ] = sums;
+        let dx = params.cell_spacing as f64;
+        let hbar = params.hbar as f64;
+        let mass = params.mass as f64;
+
+        // An empty grid has no expectation values, but a NaN anywhere should still show
+        let mean = |sum: f64| {
+            if probability == 0.0 {
+                0.0
+            } else {
+                sum / probability
+            }
+        };
+
+        Observables {
+            time,
+            total_probability: probability,
+            mean_x: mean(x) * dx,
+            mean_y: mean(y) * dx,
+            momentum_x: hbar * mean(current_x) / (2.0 * dx),
+            momentum_y: hbar * mean(current_y) / (2.0 * dx),
+            kinetic_energy: hbar * hbar / (2.0 * mass * dx * dx) * mean(gradient),
+            potential_energy: mean(potential),
+        }
+    }
+
+    /// Adds up the partial sums that each workgroup of `observables.glsl` wrote for one step.
+    pub fn from_partial_sums(partial_sums: &[[f32; 4]], time: f64, params: &StepParams) -> Self {
+        let mut sums = [0.0; 8];
+        for pair in partial_sums.chunks_exact(2) {
+            for (sum, &value) in sums.iter_mut().zip(pair[0].iter().chain(&pair[1])) {
+                *sum += value as f64;
+            }
+        }
+        Self::from_sums(sums, time, params)
+    }
+
+    /// Measures `grid` on the CPU, the same way `observables.glsl` does.
+    pub fn from_grid(grid: &Grid, time: f64, params: &StepParams) -> Self {
+        let (width, height) = (grid.width as i64, grid.height as i64);
+        let bc = params.boundary_condition;
+
+        // Ψ at a neighbouring cell, with Neumann edges reading as the cell at the edge
+        let psi = |x: i64, y: i64| -> (f64, f64) {
+            let (x, y) = match bc {
+                BoundaryCondition::Neumann => (x.clamp(0, width - 1), y.clamp(0, height - 1)),
+                BoundaryCondition::Periodic => (x.rem_euclid(width), y.rem_euclid(height)),
+                BoundaryCondition::Dirichlet | BoundaryCondition::Absorbing => {
+                    if x < 0 || x >= width || y < 0 || y >= height {
+                        return (0.0, 0.0);
+                    }
+                    (x, y)
+                }
+            };
+            let [u, v, _, _] = grid.cells[(y * width + x) as usize];
+            (u as f64, v as f64)
+        };
+
+        let mut sums = [0.0; 8];
+        for y in 0..height {
+            for x in 0..width {
+                let i = (y * width + x) as usize;
+                let potential = grid.cells[i][2] as f64;
+                let probability = grid.probability(i) as f64;
+                let (u, v) = psi(x, y);
+
+                let (left, right) = (psi(x - 1, y), psi(x + 1, y));
+                let (up, down) = (psi(x, y - 1), psi(x, y + 1));
+
+                let cell = [
+                    probability,
+                    x as f64 * probability,
+                    y as f64 * probability,
+                    u * (right.1 - left.1) - v * (right.0 - left.0),
+                    u * (down.1 - up.1) - v * (down.0 - up.0),
+                    (right.0 - u).powi(2)
+                        + (right.1 - v).powi(2)
+                        + (down.0 - u).powi(2)
+                        + (down.1 - v).powi(2),
+                    potential * probability,
+                    0.0,
+                ];
+                for (sum, value) in sums.iter_mut().zip(cell) {
+                    *sum += value;
+                }
+            }
+        }
+
+        Self::from_sums(sums, time, params)
+    }
+}
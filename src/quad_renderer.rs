@@ -10,7 +10,7 @@ use vulkano::pipeline::layout::*;
 use vulkano::pipeline::*;
 use vulkano::render_pass::*;
 use vulkano::shader::*;
-use vulkano::sync::GpuFuture;
+use vulkano::sync::{self, GpuFuture};
 
 use std::sync::Arc;
 use std::time::{Duration, Instant};
@@ -38,6 +38,10 @@ pub struct QuadRenderer {
     pipeline: Arc<GraphicsPipeline>,
 
     descriptor_set: Arc<DescriptorSet>,
+    /// The simulator's grid view that `descriptor_set` was written with.
+    grid_view: Arc<ImageView>,
+    /// The simulator's drive buffer that `descriptor_set` was written with.
+    drive_buffer: Subbuffer<[[f32; 4]]>,
 
     vertex_buffer: Subbuffer<[VertexContainer]>,
 
@@ -75,7 +79,7 @@ impl QuadRenderer {
         let vs = vs::load(mgr.context.device().clone()).expect("failed to create vertex shader module");
         let fs = fs::load(mgr.context.device().clone()).expect("failed to create fragment shader module");
 
-        let viewport = Self::get_viewport(window_size.width as f32, window_size.height as f32, ui_state.gui_width);
+        let viewport = Self::get_viewport(window_size.width as f32, window_size.height as f32, ui_state.gui_width, simulator);
         let render_pass = Self::get_render_pass(mgr);
         let framebuffers = Self::get_framebuffers(window_renderer.swapchain_image_views(), &render_pass);
         let pipeline = Self::get_pipeline(mgr, &vs, &fs, &render_pass, viewport.clone());
@@ -91,6 +95,8 @@ impl QuadRenderer {
             framebuffers,
             pipeline,
             descriptor_set,
+            grid_view: simulator.grid_view.clone(),
+            drive_buffer: simulator.drive_buffer.clone(),
 
             vertex_buffer,
             window_resized: false,
@@ -98,7 +104,7 @@ impl QuadRenderer {
         }
     }
 
-    pub fn draw(&mut self, mgr: &mut VulkanManager, simulator: &Simulator, ui_state: &mut UIState) {
+    pub fn draw(&mut self, mgr: &mut VulkanManager, simulator: &mut Simulator, ui_state: &mut UIState) {
         let mut recreate_swapchain = false;
         let previous_frame_end = mgr.windows.get_primary_renderer_mut().unwrap()
             .acquire(Some(Duration::from_millis(1000)), |_| {
@@ -107,17 +113,38 @@ impl QuadRenderer {
             .unwrap();
         
         if recreate_swapchain || self.window_resized {
-            if self.window_resized {
-                if self.last_resize_event.elapsed().as_millis() > 30 {
-                    mgr.windows.get_primary_renderer_mut().unwrap().resize();
-                    self.window_resized = false;
-                }
+            if self.window_resized && self.last_resize_event.elapsed().as_millis() > 30 {
+                mgr.windows.get_primary_renderer_mut().unwrap().resize();
+                self.window_resized = false;
             }
             self.update(mgr, simulator, ui_state);
         }
+
+        // The simulator reallocates its grid when it is resized or a differently sized state is
+        // loaded, which also changes the viewport, and replaces its drive buffer when the drives
+        // change
+        if !Arc::ptr_eq(&self.grid_view, &simulator.grid_view)
+            || !Arc::ptr_eq(self.drive_buffer.buffer(), simulator.drive_buffer.buffer())
+        {
+            self.update(mgr, simulator, ui_state);
+        }
         
-        let command_buffer = self.command_buffer(&mgr, ui_state);
+        let command_buffer = self.command_buffer(mgr, simulator, ui_state);
         
+        // Run the steps recorded for this frame on the compute queue, and only draw the grid once
+        // they have finished, without waiting for them here
+        let previous_frame_end = match simulator.take_commands() {
+            Some(commands) => {
+                let compute_future = sync::now(mgr.context.device().clone())
+                    .then_execute(mgr.context.compute_queue().clone(), commands)
+                    .unwrap()
+                    .then_signal_semaphore_and_flush()
+                    .unwrap();
+                previous_frame_end.join(compute_future).boxed()
+            }
+            None => previous_frame_end,
+        };
+
         let mut future = previous_frame_end
             .then_execute(mgr.context.graphics_queue().clone(), command_buffer.clone())
             .unwrap()
@@ -134,7 +161,7 @@ impl QuadRenderer {
         let window_renderer = mgr.windows.get_primary_renderer().unwrap();
         let window_size = window_renderer.window().inner_size();
         
-        self.viewport = Self::get_viewport(window_size.width as f32, window_size.height as f32, ui_state.gui_width);
+        self.viewport = Self::get_viewport(window_size.width as f32, window_size.height as f32, ui_state.gui_width, simulator);
         self.pipeline = Self::get_pipeline(
             mgr,
             &self.vertex_shader,
@@ -143,10 +170,12 @@ impl QuadRenderer {
             self.viewport.clone(),
         );
         self.descriptor_set = Self::get_descriptor_set(mgr, simulator, &self.pipeline);
+        self.grid_view = simulator.grid_view.clone();
+        self.drive_buffer = simulator.drive_buffer.clone();
         self.framebuffers = Self::get_framebuffers(window_renderer.swapchain_image_views(), &self.render_pass);
     }
 
-    pub fn command_buffer(&self, mgr: &VulkanManager, ui_state: &UIState) -> Arc<PrimaryAutoCommandBuffer> {
+    pub fn command_buffer(&self, mgr: &VulkanManager, simulator: &Simulator, ui_state: &UIState) -> Arc<PrimaryAutoCommandBuffer> {
         let mut builder = AutoCommandBufferBuilder::primary(
             mgr.command_buffer_allocator.clone(),
             mgr.context.graphics_queue().queue_family_index(),
@@ -154,8 +183,15 @@ impl QuadRenderer {
         )
         .unwrap();
 
+        let params = ui_state.step_params();
         let push_constants = fs::PushConstantData {
             visible_layer: ui_state.visible_layer as i32,
+            time: simulator.time() as f32,
+            drive_count: simulator.drives().len() as i32,
+            hbar_over_mass: params.hbar / params.mass,
+            cell_spacing: params.cell_spacing,
+            current_scale: ui_state.current_scale,
+            current_arrows: ui_state.current_arrows as i32,
         };
         
         let window_renderer = mgr.windows.get_primary_renderer().unwrap();
@@ -195,10 +231,16 @@ impl QuadRenderer {
         builder.build().unwrap()
     }
 
-    fn get_viewport(window_width: f32, window_height: f32, gui_width: f32) -> viewport::Viewport {
+    /// The largest rectangle to the right of the side panel that has the same aspect ratio as
+    /// the simulation grid, centred in the space that is left.
+    pub fn get_viewport(window_width: f32, window_height: f32, gui_width: f32, simulator: &Simulator) -> viewport::Viewport {
+        let area = [(window_width - gui_width).max(gui_width), window_height];
+        let scale = f32::min(area[0] / simulator.width as f32, area[1] / simulator.height as f32);
+        let extent = [simulator.width as f32 * scale, simulator.height as f32 * scale];
+
         viewport::Viewport {
-            offset: [gui_width, 0.0],
-            extent: [(window_width - gui_width).max(gui_width), window_height],
+            offset: [gui_width + (area[0] - extent[0]) / 2.0, (area[1] - extent[1]) / 2.0],
+            extent,
             depth_range: 0.0..=1.0,
         }
     }
@@ -297,6 +339,8 @@ impl QuadRenderer {
             [
                 WriteDescriptorSet::sampler(0, simulator.grid_sampler.clone()),
                 WriteDescriptorSet::image_view(1, simulator.grid_view.clone()),
+                WriteDescriptorSet::buffer(2, simulator.drive_buffer.clone()),
+                WriteDescriptorSet::image_view(3, simulator.momentum_view.clone()),
             ],
             [],
         )
@@ -0,0 +1,603 @@
+use std::fs;
+use std::ops::RangeInclusive;
+use std::path::Path;
+
+use toml_edit::{Document, Item, TableLike};
+
+use crate::drive::{Drive, DrivenPotential};
+use crate::grid::Grid;
+use crate::solver::StepParams;
+use crate::ui_state::{BoundaryCondition, Integrator, Units};
+use crate::wave_packet::WavePacket;
+
+/// The most cells a scene can ask for along each side, which is as large as Vulkan guarantees
+/// that images can be.
+const MAX_GRID_SIZE: u32 = 4096;
+
+/// A region of potential energy described by a scene file. All coordinates are in grid cells,
+/// regardless of the physical cell spacing.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum PotentialShape {
+    Rectangle {
+        x: f32,
+        y: f32,
+        width: f32,
+        height: f32,
+        value: f32,
+    },
+    Circle {
+        x: f32,
+        y: f32,
+        radius: f32,
+        value: f32,
+    },
+    /// A vertical wall at `x` with `count` evenly spaced openings centred on `center`.
+    Slits {
+        x: f32,
+        thickness: f32,
+        value: f32,
+        count: u32,
+        slit_width: f32,
+        slit_spacing: f32,
+        center: Option<f32>,
+    },
+    /// `0.5 * k * r²` around `(x, y)`.
+    Harmonic { x: f32, y: f32, k: f32 },
+    /// A uniform force, the potential `-F·(r - r₀)` that is zero at `(x, y)`. The force is an
+    /// energy per cell.
+    Field {
+        x: f32,
+        y: f32,
+        force_x: f32,
+        force_y: f32,
+    },
+}
+
+impl PotentialShape {
+    /// The potential contributed by this shape at the cell `(x, y)` of a grid `height` cells tall.
+    pub fn value(&self, x: f32, y: f32, height: f32) -> f32 {
+        match *self {
+            PotentialShape::Rectangle {
+                x: left,
+                y: top,
+                width,
+                height,
+                value,
+            } => {
+                let inside = x >= left && x < left + width && y >= top && y < top + height;
+                if inside { value } else { 0.0 }
+            }
+            PotentialShape::Circle {
+                x: cx,
+                y: cy,
+                radius,
+                value,
+            } => {
+                if f32::hypot(x - cx, y - cy) <= radius {
+                    value
+                } else {
+                    0.0
+                }
+            }
+            PotentialShape::Slits {
+                x: wall_x,
+                thickness,
+                value,
+                count,
+                slit_width,
+                slit_spacing,
+                center,
+            } => {
+                if x < wall_x || x >= wall_x + thickness {
+                    return 0.0;
+                }
+
+                // Slit centres are `slit_spacing` apart, symmetric about the centre of the wall
+                let center = center.unwrap_or(height / 2.0);
+                let first = center - slit_spacing * (count as f32 - 1.0) / 2.0;
+                let in_slit = (0..count).any(|i| {
+                    let slit_center = first + slit_spacing * i as f32;
+                    (y - slit_center).abs() < slit_width / 2.0
+                });
+
+                if in_slit { 0.0 } else { value }
+            }
+            PotentialShape::Harmonic { x: cx, y: cy, k } => {
+                0.5 * k * ((x - cx).powi(2) + (y - cy).powi(2))
+            }
+            PotentialShape::Field {
+                x: x0,
+                y: y0,
+                force_x,
+                force_y,
+            } => -(force_x * (x - x0) + force_y * (y - y0)),
+        }
+    }
+}
+
+/// A reproducible set of initial conditions loaded from a TOML file.
+///
+/// ```toml
+/// time_step = 0.18
+/// boundary_condition = "neumann"
+/// integrator = "leapfrog"
+/// width = 256
+/// height = 256
+/// cell_spacing = 1.0
+/// units = "natural"
+/// hbar = 1.0
+/// mass = 1.0
+///
+/// [[packet]]
+/// x = 64
+/// y = 128
+/// sigma = 2.5
+/// amplitude = 8
+/// theta = 0.0
+/// speed = 2.0
+///
+/// [[potential]]
+/// shape = "slits"
+/// x = 128
+/// thickness = 4
+/// value = 10
+/// count = 2
+/// slit_width = 6
+/// slit_spacing = 20
+/// ```
+///
+/// Every setting at the top is optional, and `boundary_condition = "absorbing"` also reads
+/// `absorbing_width` and `absorbing_strength`. The integrator is `"leapfrog"`,
+/// `"crank-nicolson"` or, with periodic boundaries, `"split-step"`. The units are `"natural"` or
+/// `"nanometre"` (nanometres, femtoseconds and electronvolts), and choosing them also sets ħ and
+/// the mass to their values for an electron unless these are given too. Packet speeds are in those
+/// units, and potential values are energies. Overlapping potential shapes add up.
+///
+/// The shapes are `"rectangle"`, `"circle"`, `"slits"`, `"harmonic"` and `"field"`, a uniform
+/// force with `force_x` and `force_y` that tilts the potential about `x` and `y`. Any potential
+/// can also change over time, which is evaluated at every step instead of being drawn onto the
+/// grid:
+///
+/// ```toml
+/// [[potential]]
+/// shape = "rectangle"
+/// x = 120
+/// y = 0
+/// width = 8
+/// height = 256
+/// value = 5
+/// mean = 0.5          # the shape is scaled by mean + oscillation·sin(omega·t + phase),
+/// oscillation = 0.5   # defaulting to 1 + 0·sin(0)
+/// omega = 0.2
+/// phase = 0.0
+/// pulse_center = 40   # times a Gaussian pulse, if both are given
+/// pulse_width = 10
+/// velocity_x = 0.1    # and moved by velocity·t + swing·sin(omega·t + phase)
+/// swing_y = 20
+/// ```
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Scene {
+    pub size: Option<(u32, u32)>,
+    pub time_step: Option<f32>,
+    pub boundary_condition: Option<BoundaryCondition>,
+    pub absorbing_width: Option<f32>,
+    pub absorbing_strength: Option<f32>,
+    pub cell_spacing: Option<f32>,
+    pub integrator: Option<Integrator>,
+    pub units: Option<Units>,
+    pub hbar: Option<f32>,
+    pub mass: Option<f32>,
+    pub packets: Vec<WavePacket>,
+    pub potentials: Vec<PotentialShape>,
+    /// The potentials that change over time, which are not drawn onto the grid.
+    pub drives: Vec<DrivenPotential>,
+}
+
+impl Scene {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = fs::read_to_string(path).map_err(|err| err.to_string())?;
+        Self::parse(&text)
+    }
+
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let document = Document::parse(text).map_err(|err| err.to_string())?;
+        let root = Fields::new(document.as_table(), "scene");
+        root.check_keys(&[
+            "width",
+            "height",
+            "time_step",
+            "boundary_condition",
+            "absorbing_width",
+            "absorbing_strength",
+            "cell_spacing",
+            "integrator",
+            "units",
+            "hbar",
+            "mass",
+            "packet",
+            "potential",
+        ])?;
+
+        let size = match (
+            root.integer("width", 1..=MAX_GRID_SIZE)?,
+            root.integer("height", 1..=MAX_GRID_SIZE)?,
+        ) {
+            (None, None) => None,
+            (Some(width), Some(height)) => Some((width, height)),
+            _ => {
+                return Err(String::from(
+                    "scene: width and height must be given together",
+                ));
+            }
+        };
+
+        let boundary_condition = root
+            .string("boundary_condition")?
+            .map(|bc| bc.parse::<BoundaryCondition>())
+            .transpose()
+            .map_err(|err| format!("scene: {}", err))?;
+
+        let integrator = root
+            .string("integrator")?
+            .map(|integrator| integrator.parse::<Integrator>())
+            .transpose()
+            .map_err(|err| format!("scene: {}", err))?;
+
+        let units = root
+            .string("units")?
+            .map(|units| units.parse::<Units>())
+            .transpose()
+            .map_err(|err| format!("scene: {}", err))?;
+
+        let (hbar, mass) = (root.number("hbar")?, root.number("mass")?);
+        for (key, value) in [("hbar", hbar), ("mass", mass)] {
+            if value.is_some_and(|value| value <= 0.0) {
+                return Err(format!("{}: {} must be positive", root.context, key));
+            }
+        }
+
+        let mut packets = Vec::new();
+        for (i, table) in root.tables("packet")?.into_iter().enumerate() {
+            let fields = Fields::new(table, &format!("packet {}", i + 1));
+            fields.check_keys(&["x", "y", "sigma", "amplitude", "theta", "speed"])?;
+            packets.push(WavePacket {
+                x: fields.required("x")?,
+                y: fields.required("y")?,
+                sigma: fields.required("sigma")?,
+                amplitude: fields.required("amplitude")?,
+                theta: fields.number("theta")?.unwrap_or(0.0),
+                speed: fields.number("speed")?.unwrap_or(0.0),
+            });
+        }
+
+        let mut potentials = Vec::new();
+        let mut drives = Vec::new();
+        for (i, table) in root.tables("potential")?.into_iter().enumerate() {
+            let fields = Fields::new(table, &format!("potential {}", i + 1));
+            let check_keys = |keys: &[&str]| fields.check_keys(&[keys, DRIVE_KEYS].concat());
+            let shape = match fields.string("shape")? {
+                Some("rectangle") => {
+                    check_keys(&["shape", "x", "y", "width", "height", "value"])?;
+                    PotentialShape::Rectangle {
+                        x: fields.required("x")?,
+                        y: fields.required("y")?,
+                        width: fields.required("width")?,
+                        height: fields.required("height")?,
+                        value: fields.required("value")?,
+                    }
+                }
+                Some("circle") => {
+                    check_keys(&["shape", "x", "y", "radius", "value"])?;
+                    PotentialShape::Circle {
+                        x: fields.required("x")?,
+                        y: fields.required("y")?,
+                        radius: fields.required("radius")?,
+                        value: fields.required("value")?,
+                    }
+                }
+                Some("slits") => {
+                    check_keys(&[
+                        "shape",
+                        "x",
+                        "thickness",
+                        "value",
+                        "count",
+                        "slit_width",
+                        "slit_spacing",
+                        "center",
+                    ])?;
+                    PotentialShape::Slits {
+                        x: fields.required("x")?,
+                        thickness: fields.required("thickness")?,
+                        value: fields.required("value")?,
+                        count: fields
+                            .integer("count", 0..=MAX_GRID_SIZE)?
+                            .ok_or_else(|| format!("{}: missing count", fields.context))?,
+                        slit_width: fields.required("slit_width")?,
+                        slit_spacing: fields.number("slit_spacing")?.unwrap_or(0.0),
+                        center: fields.number("center")?,
+                    }
+                }
+                Some("harmonic") => {
+                    check_keys(&["shape", "x", "y", "k"])?;
+                    PotentialShape::Harmonic {
+                        x: fields.required("x")?,
+                        y: fields.required("y")?,
+                        k: fields.required("k")?,
+                    }
+                }
+                Some("field") => {
+                    check_keys(&["shape", "x", "y", "force_x", "force_y"])?;
+                    PotentialShape::Field {
+                        x: fields.required("x")?,
+                        y: fields.required("y")?,
+                        force_x: fields.number("force_x")?.unwrap_or(0.0),
+                        force_y: fields.number("force_y")?.unwrap_or(0.0),
+                    }
+                }
+                Some(other) => return Err(format!("{}: unknown shape {}", fields.context, other)),
+                None => return Err(format!("{}: missing shape", fields.context)),
+            };
+            match parse_drive(&fields)? {
+                Some(drive) => drives.push(DrivenPotential::Shape { shape, drive }),
+                None => potentials.push(shape),
+            }
+        }
+
+        Ok(Scene {
+            size,
+            time_step: root.number("time_step")?,
+            boundary_condition,
+            absorbing_width: root.number("absorbing_width")?,
+            absorbing_strength: root.number("absorbing_strength")?,
+            cell_spacing: root.number("cell_spacing")?,
+            integrator,
+            units,
+            hbar,
+            mass,
+            packets,
+            potentials,
+            drives,
+        })
+    }
+
+    /// Overrides the parameters in `params` with the ones this scene sets.
+    pub fn step_params(&self, params: StepParams) -> StepParams {
+        StepParams {
+            time_step: self.time_step.unwrap_or(params.time_step),
+            boundary_condition: self.boundary_condition.unwrap_or(params.boundary_condition),
+            absorbing_width: self.absorbing_width.unwrap_or(params.absorbing_width),
+            absorbing_strength: self.absorbing_strength.unwrap_or(params.absorbing_strength),
+            cell_spacing: self.cell_spacing.unwrap_or(params.cell_spacing),
+            integrator: self.integrator.unwrap_or(params.integrator),
+            units: self.units.unwrap_or(params.units),
+            hbar: self
+                .hbar
+                .or(self.units.map(|units| units.hbar()))
+                .unwrap_or(params.hbar),
+            mass: self
+                .mass
+                .or(self.units.map(|units| units.electron_mass()))
+                .unwrap_or(params.mass),
+        }
+    }
+
+    /// Draws the static potentials and wave packets of the scene onto `grid`, giving the packets
+    /// their momentum with the mass and ħ in `params`. The [`Self::drives`] are left to the
+    /// solver.
+    pub fn apply(&self, grid: &mut Grid, params: &StepParams) {
+        let height = grid.height as f32;
+        for y in 0..grid.height {
+            for x in 0..grid.width {
+                let mut cell = grid.get(x, y);
+                cell[2] += self
+                    .potentials
+                    .iter()
+                    .map(|shape| shape.value(x as f32, y as f32, height))
+                    .sum::<f32>();
+                grid.set(x, y, cell);
+            }
+        }
+
+        for packet in &self.packets {
+            packet.apply(grid, params);
+        }
+    }
+
+    /// Builds a new grid holding only this scene, using the scene's size if it has one.
+    pub fn build_grid(&self, width: u32, height: u32, params: &StepParams) -> Grid {
+        let (width, height) = self.size.unwrap_or((width, height));
+        let mut grid = Grid::new(width, height);
+        self.apply(&mut grid, params);
+        grid
+    }
+}
+
+/// The keys that make a `[[potential]]` time-dependent.
+const DRIVE_KEYS: &[&str] = &[
+    "mean",
+    "oscillation",
+    "omega",
+    "phase",
+    "pulse_center",
+    "pulse_width",
+    "velocity_x",
+    "velocity_y",
+    "swing_x",
+    "swing_y",
+];
+
+/// Reads how a potential changes over time, or `None` if it has none of the [`DRIVE_KEYS`].
+fn parse_drive(fields: &Fields) -> Result<Option<Drive>, String> {
+    if !DRIVE_KEYS.iter().any(|key| fields.table.contains_key(key)) {
+        return Ok(None);
+    }
+
+    let pulse = match (
+        fields.number("pulse_center")?,
+        fields.number("pulse_width")?,
+    ) {
+        (None, None) => None,
+        (Some(center), Some(width)) if width > 0.0 => Some((center, width)),
+        _ => {
+            return Err(format!(
+                "{}: pulse_center and pulse_width must be given together, with a positive width",
+                fields.context
+            ));
+        }
+    };
+
+    let defaults = Drive::default();
+    let number = |key: &str, default: f32| -> Result<f32, String> {
+        Ok(fields.number(key)?.unwrap_or(default))
+    };
+    Ok(Some(Drive {
+        mean: number("mean", defaults.mean)?,
+        oscillation: number("oscillation", defaults.oscillation)?,
+        omega: number("omega", defaults.omega)?,
+        phase: number("phase", defaults.phase)?,
+        pulse,
+        velocity: (number("velocity_x", 0.0)?, number("velocity_y", 0.0)?),
+        swing: (number("swing_x", 0.0)?, number("swing_y", 0.0)?),
+    }))
+}
+
+/// Typed access to the keys of one table, with errors that name the table they came from.
+struct Fields<'a> {
+    table: &'a dyn TableLike,
+    context: String,
+}
+
+impl<'a> Fields<'a> {
+    fn new(table: &'a dyn TableLike, context: &str) -> Self {
+        Fields {
+            table,
+            context: String::from(context),
+        }
+    }
+
+    fn check_keys(&self, allowed: &[&str]) -> Result<(), String> {
+        match self.table.iter().find(|(key, _)| !allowed.contains(key)) {
+            Some((key, _)) => Err(format!("{}: unknown key {}", self.context, key)),
+            None => Ok(()),
+        }
+    }
+
+    fn number(&self, key: &str) -> Result<Option<f32>, String> {
+        match self.table.get(key) {
+            None => Ok(None),
+            Some(item) => item
+                .as_float()
+                .or_else(|| item.as_integer().map(|i| i as f64))
+                .map(|value| Some(value as f32))
+                .ok_or_else(|| format!("{}: {} must be a number", self.context, key)),
+        }
+    }
+
+    /// A whole number in `range`. Unlike [`Self::number`] this rejects fractions instead of
+    /// rounding them.
+    fn integer(&self, key: &str, range: RangeInclusive<u32>) -> Result<Option<u32>, String> {
+        let Some(item) = self.table.get(key) else {
+            return Ok(None);
+        };
+        let value = item
+            .as_integer()
+            .ok_or_else(|| format!("{}: {} must be a whole number", self.context, key))?;
+        u32::try_from(value)
+            .ok()
+            .filter(|value| range.contains(value))
+            .map(Some)
+            .ok_or_else(|| {
+                format!(
+                    "{}: {} must be between {} and {}",
+                    self.context,
+                    key,
+                    range.start(),
+                    range.end()
+                )
+            })
+    }
+
+    fn required(&self, key: &str) -> Result<f32, String> {
+        self.number(key)?
+            .ok_or_else(|| format!("{}: missing {}", self.context, key))
+    }
+
+    fn string(&self, key: &str) -> Result<Option<&'a str>, String> {
+        match self.table.get(key) {
+            None => Ok(None),
+            Some(item) => item
+                .as_str()
+                .map(Some)
+                .ok_or_else(|| format!("{}: {} must be a string", self.context, key)),
+        }
+    }
+
+    fn tables(&self, key: &str) -> Result<Vec<&'a dyn TableLike>, String> {
+        match self.table.get(key) {
+            None => Ok(Vec::new()),
+            Some(Item::ArrayOfTables(tables)) => {
+                Ok(tables.iter().map(|table| table as &dyn TableLike).collect())
+            }
+            Some(_) => Err(format!(
+                "{}: {} must be written as [[{}]]",
+                self.context, key, key
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn size() {
+        let scene = Scene::parse("width = 300\nheight = 200").unwrap();
+        assert_eq!(scene.size, Some((300, 200)));
+        assert_eq!(Scene::parse("").unwrap().size, None);
+
+        assert_eq!(
+            Scene::parse("width = 300.5\nheight = 200").unwrap_err(),
+            "scene: width must be a whole number"
+        );
+        assert_eq!(
+            Scene::parse("width = 300\nheight = 0").unwrap_err(),
+            "scene: height must be between 1 and 4096"
+        );
+        assert_eq!(
+            Scene::parse("width = -4\nheight = 200").unwrap_err(),
+            "scene: width must be between 1 and 4096"
+        );
+        assert_eq!(
+            Scene::parse("width = 300").unwrap_err(),
+            "scene: width and height must be given together"
+        );
+    }
+
+    #[test]
+    fn slit_count() {
+        let slits = |count: &str| {
+            Scene::parse(&format!(
+                "[[potential]]\nshape = \"slits\"\nx = 10\nthickness = 2\nvalue = 5\n\
+                 slit_width = 4\n{}",
+                count
+            ))
+        };
+        let scene = slits("count = 2").unwrap();
+        assert!(matches!(
+            scene.potentials[..],
+            [PotentialShape::Slits { count: 2, .. }]
+        ));
+
+        assert_eq!(
+            slits("count = -1").unwrap_err(),
+            "potential 1: count must be between 0 and 4096"
+        );
+        assert_eq!(
+            slits("count = 2.5").unwrap_err(),
+            "potential 1: count must be a whole number"
+        );
+        assert_eq!(slits("").unwrap_err(), "potential 1: missing count");
+    }
+}
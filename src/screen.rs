@@ -0,0 +1,133 @@
+use crate::grid::Grid;
+use crate::random::Random;
+
+/// A line across the grid on which single particles are detected, one hit at a time, with the
+/// probability of a hit at each point along it proportional to |Ψ|² there. Many hits build up the
+/// interference pattern the way they do on the screen behind a double slit.
+#[derive(Debug, Clone)]
+pub struct DetectionScreen {
+    /// The ends of the line in cell coordinates, measured from the centre of the top left cell.
+    pub start: (f32, f32),
+    pub end: (f32, f32),
+    /// Every hit so far, in the order they happened.
+    pub hits: Vec<Hit>,
+    seed: u64,
+    random: Random,
+}
+
+/// Where a particle was detected.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Hit {
+    /// How far along the screen the hit is, from 0 at the start to 1 at the end.
+    pub position: f32,
+    /// A random value in `[0, 1)` that spreads the dots of the hits out across the line, so that
+    /// they do not all pile up on top of each other when drawn.
+    pub spread: f32,
+}
+
+impl DetectionScreen {
+    pub fn new(start: (f32, f32), end: (f32, f32), seed: u64) -> Self {
+        DetectionScreen {
+            start,
+            end,
+            hits: Vec::new(),
+            seed,
+            random: Random::new(seed),
+        }
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Forgets every hit and starts the random numbers over from `seed`, so that the same wave
+    /// function gives the same hits again.
+    pub fn reset(&mut self, seed: u64) {
+        self.hits.clear();
+        self.seed = seed;
+        self.random = Random::new(seed);
+    }
+
+    pub fn length(&self) -> f32 {
+        ((self.end.0 - self.start.0).powi(2) + (self.end.1 - self.start.1).powi(2)).sqrt()
+    }
+
+    /// The point a fraction `t` of the way from the start to the end, in cell coordinates.
+    pub fn point(&self, t: f32) -> (f32, f32) {
+        (
+            self.start.0 + t * (self.end.0 - self.start.0),
+            self.start.1 + t * (self.end.1 - self.start.1),
+        )
+    }
+
+    /// |Ψ|² at points about one cell apart along the line, from the start to the end, bilinearly
+    /// interpolated between the cells.
+    pub fn density(&self, grid: &Grid) -> Vec<f64> {
+        let samples = self.length().ceil().max(1.0) as usize + 1;
+        (0..samples)
+            .map(|i| {
+                let (x, y) = self.point(i as f32 / (samples - 1) as f32);
+                interpolated_probability(grid, x, y)
+            })
+            .collect()
+    }
+
+    /// Detects `count` particles in `grid`, and returns how many hits there were, which is none
+    /// if there is no probability along the line at all.
+    ///
+    /// Each hit picks a piece of the line between two samples of [`DetectionScreen::density`]
+    /// with a probability proportional to the average |Ψ|² over it, and then a point on that
+    /// piece uniformly.
+    pub fn detect(&mut self, grid: &Grid, count: u32) -> u32 {
+        let density = self.density(grid);
+        let mut cumulative = Vec::with_capacity(density.len() - 1);
+        let mut total = 0.0;
+        for pair in density.windows(2) {
+            total += 0.5 * (pair[0] + pair[1]);
+            cumulative.push(total);
+        }
+        if !total.is_finite() || total <= 0.0 {
+            return 0;
+        }
+
+        let pieces = cumulative.len();
+        for _ in 0..count {
+            let target = self.random.next_f64() * total;
+            let piece = cumulative
+                .partition_point(|&sum| sum <= target)
+                .min(pieces - 1);
+            let within = self.random.next_f64();
+            self.hits.push(Hit {
+                position: ((piece as f64 + within) / pieces as f64) as f32,
+                spread: self.random.next_f64() as f32,
+            });
+        }
+        count
+    }
+
+    /// The number of hits in each of `bins` equal parts of the line.
+    pub fn histogram(&self, bins: usize) -> Vec<u32> {
+        let mut counts = vec![0; bins];
+        for hit in &self.hits {
+            let bin = ((hit.position * bins as f32) as usize).min(bins - 1);
+            counts[bin] += 1;
+        }
+        counts
+    }
+}
+
+/// |Ψ|² at a point in cell coordinates, bilinearly interpolated between the four nearest cells,
+/// with points outside of the grid taking the value at the edge. The leapfrog |Ψ|² can dip just
+/// below 0, which counts as 0.
+fn interpolated_probability(grid: &Grid, x: f32, y: f32) -> f64 {
+    let x = x.clamp(0.0, (grid.width - 1) as f32);
+    let y = y.clamp(0.0, (grid.height - 1) as f32);
+    let (x0, y0) = (x.floor() as u32, y.floor() as u32);
+    let (x1, y1) = ((x0 + 1).min(grid.width - 1), (y0 + 1).min(grid.height - 1));
+    let (fx, fy) = (x - x0 as f32, y - y0 as f32);
+
+    let at = |x: u32, y: u32| grid.probability((y * grid.width + x) as usize).max(0.0);
+    let top = at(x0, y0) * (1.0 - fx) + at(x1, y0) * fx;
+    let bottom = at(x0, y1) * (1.0 - fx) + at(x1, y1) * fx;
+    (top * (1.0 - fy) + bottom * fy) as f64
+}
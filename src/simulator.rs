@@ -1,4 +1,8 @@
-use vulkano::command_buffer::ClearColorImageInfo;
+use vulkano::buffer::{Buffer, BufferCreateInfo, BufferUsage, Subbuffer};
+use vulkano::command_buffer::{
+    AutoCommandBufferBuilder, ClearColorImageInfo, CopyBufferToImageInfo, CopyImageInfo,
+    CopyImageToBufferInfo, PrimaryAutoCommandBuffer,
+};
 use vulkano::descriptor_set::{DescriptorSet, WriteDescriptorSet};
 use vulkano::format::*;
 use vulkano::image::sampler::{Sampler, SamplerAddressMode, SamplerCreateInfo};
@@ -10,52 +14,188 @@ use vulkano::pipeline::layout::PipelineDescriptorSetLayoutCreateInfo;
 use vulkano::pipeline::{
     ComputePipeline, Pipeline, PipelineBindPoint, PipelineLayout, PipelineShaderStageCreateInfo,
 };
+use vulkano::shader::ShaderModule;
 
+use std::collections::VecDeque;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use crate::app::VulkanManager;
-use crate::ui_state::UIState;
+use crate::detector::{self, Detector, DetectorSample, MAX_DETECTORS, Reading};
+use crate::drive::{self, DrivenPotential, DrivenSolver};
+use crate::eigenstates::EigenstateSearch;
+use crate::grid::Grid;
+use crate::momentum;
+use crate::observables::{Instability, Observables};
+use crate::solver::{self, Solver, StepParams};
+use crate::ui_state::{BrushMode, Integrator, SimulationLayer, UIState};
+
+/// The width and height of the compute kernel's workgroups, which must match `TILE_SIZE` in
+/// `compute.glsl`.
+const TILE_SIZE: u32 = 16;
+
+/// The most steps of a single submission that are measured, the last ones of it. Advancing by
+/// many steps at once would otherwise need a buffer of partial sums for every one of them.
+const MAX_MEASURED_STEPS: u32 = 64;
+
+/// The number of steps between the snapshots that the stability guard rolls back to.
+const SNAPSHOT_INTERVAL: u64 = 100;
+
+/// The shortest time between two updates of the Momentum layer, which copy the grid back and
+/// transform it on the CPU, so that they do not hold up every frame while the simulation runs.
+const MOMENTUM_INTERVAL: Duration = Duration::from_millis(250);
+
+/// The observables of a batch of steps, in whichever form they were measured.
+enum Measurements {
+    /// The partial sums written by `observables.glsl` for each measured step, which can only be
+    /// read once the submission that writes them has finished.
+    Gpu {
+        sums: Subbuffer<[[f32; 4]]>,
+        groups: usize,
+        times: Vec<f64>,
+        params: StepParams,
+    },
+    /// Observables measured on the CPU, which are ready straight away.
+    Cpu(Vec<Observables>),
+}
+
+/// Observables that have been measured but not yet taken with [`Simulator::take_observables`].
+struct PendingObservables {
+    measurements: Measurements,
+    /// The [`Simulator::generation`] they were measured in.
+    generation: u64,
+    /// A copy of the grid after the last measured step, which becomes the snapshot to roll back
+    /// to if none of the measurements show any instability.
+    snapshot: Option<Snapshot>,
+}
+
+/// The detector readings of a batch of steps, with the outflow counted from the start of the
+/// batch, in whichever form they were measured.
+enum DetectorMeasurements {
+    /// The partial sums written by `detectors.glsl` for each measured step.
+    Gpu {
+        sums: Subbuffer<[[f32; 4]]>,
+        groups: usize,
+        times: Vec<f64>,
+    },
+    /// Readings measured on the CPU.
+    Cpu(Vec<DetectorSample>),
+}
+
+/// Detector readings that have not yet been taken with [`Simulator::take_detections`].
+struct PendingDetections {
+    measurements: DetectorMeasurements,
+    /// The [`Simulator::detector_generation`] they were measured in.
+    generation: u64,
+}
+
+/// A copy of the grid image, along with the clock when it was taken.
+struct Snapshot {
+    image: Arc<Image>,
+    step_count: u64,
+    time: f64,
+}
+
+/// What the stability guard did when the simulation became unstable, see
+/// [`Simulator::take_rollback`].
+#[derive(Debug, Copy, Clone)]
+pub struct Rollback {
+    pub instability: Instability,
+    /// The simulation time at which the instability was measured.
+    pub time: f64,
+    /// The simulation time of the snapshot that the grid was rolled back to, or `None` if there
+    /// was no snapshot yet and the grid was left as it is.
+    pub restored_time: Option<f64>,
+}
 
 pub struct Simulator {
     grid_u: Arc<Image>,
     pub grid_view: Arc<ImageView>,
     pub grid_sampler: Arc<Sampler>,
+    /// The momentum-space wave function from [`momentum::momentum_grid`], for the Momentum layer.
+    /// It is only filled in by [`Self::update_momentum`].
+    momentum_u: Arc<Image>,
+    pub momentum_view: Arc<ImageView>,
+    /// Counts the changes to the grid, by steps, the brush or by hand.
+    grid_changes: u64,
+    /// The [`Self::grid_changes`] that the momentum-space wave function was computed at.
+    momentum_changes: Option<u64>,
+    /// When the momentum-space wave function was last computed.
+    momentum_updated: Option<Instant>,
 
     pipeline: Arc<ComputePipeline>,
+    observables_pipeline: Arc<ComputePipeline>,
+    detectors_pipeline: Arc<ComputePipeline>,
 
     pub width: u32,
     pub height: u32,
+
+    step_count: u64,
+    time: f64,
+
+    pending_commands: Option<Arc<PrimaryAutoCommandBuffer>>,
+
+    /// Whether to measure the [`Observables`] after each step.
+    pub measure_observables: bool,
+    pending_observables: VecDeque<PendingObservables>,
+
+    /// Whether to roll back to the last snapshot once the measurements show the simulation
+    /// becoming unstable. Only works while [`Self::measure_observables`] is set too.
+    pub stability_guard: bool,
+    /// The last snapshot after which no instability was measured.
+    snapshot: Option<Snapshot>,
+    /// The step count of the last snapshot taken, whether or not it has been checked yet.
+    last_snapshot_step: Option<u64>,
+    /// The total probability when the wave function was last changed by hand, which any growth
+    /// is measured against.
+    baseline_probability: Option<f64>,
+    /// Counts the changes to the wave function by hand. Measurements from before the last one
+    /// are not checked for instability, since the probability may have changed in between.
+    generation: u64,
+    rollback: Option<Rollback>,
+
+    /// The largest `|V|` on the grid. While the brush draws on the potential it is raised by as
+    /// much as each frame of the stroke could, and it is only made exact again once the stroke
+    /// has ended, see [`Self::end_stroke`].
+    max_potential: f32,
+    /// Whether the brush has drawn on the potential since the mouse was pressed.
+    potential_stroke: bool,
+
+    /// The potentials that change over time, which are added to the one in the grid at every
+    /// step.
+    drives: Vec<DrivenPotential>,
+    /// The drives packed for `drives.glsl`.
+    pub drive_buffer: Subbuffer<[[f32; 4]]>,
+    /// A bound on the largest `|V|` that the drives add.
+    max_drive_potential: f32,
+
+    /// The regions that are measured after every step.
+    detectors: Vec<Detector>,
+    /// The detectors packed for `detectors.glsl`.
+    detector_buffer: Subbuffer<[[f32; 4]]>,
+    /// The probability that has left each detector since they were last reset.
+    outflow: Vec<f64>,
+    /// Counts the changes to the detectors, so that readings of the old ones can be dropped.
+    detector_generation: u64,
+    pending_detections: VecDeque<PendingDetections>,
 }
 
 impl Simulator {
-    pub fn new(mgr: &VulkanManager) -> Self {
-        let width = 1;
-        let height = 1;
-
-        let pipeline = {
-            let cs = cs::load(mgr.context.device().clone())
-                .unwrap()
-                .entry_point("main")
-                .unwrap();
-            let stage = PipelineShaderStageCreateInfo::new(cs);
-            let layout = PipelineLayout::new(
-                mgr.context.device().clone(),
-                PipelineDescriptorSetLayoutCreateInfo::from_stages([&stage])
-                    .into_pipeline_layout_create_info(mgr.context.device().clone())
-                    .unwrap(),
-            )
-            .unwrap();
-
-            ComputePipeline::new(
-                mgr.context.device().clone(),
-                None,
-                ComputePipelineCreateInfo::stage_layout(stage, layout),
-            )
-            .unwrap()
-        };
+    pub fn new(mgr: &VulkanManager, width: u32, height: u32) -> Self {
+        let pipeline = Self::get_pipeline(mgr, cs::load(mgr.context.device().clone()).unwrap());
+        let observables_pipeline = Self::get_pipeline(
+            mgr,
+            observables_cs::load(mgr.context.device().clone()).unwrap(),
+        );
+        let detectors_pipeline = Self::get_pipeline(
+            mgr,
+            detectors_cs::load(mgr.context.device().clone()).unwrap(),
+        );
 
         let grid_u = Self::get_grid_image(mgr, width, height);
         let grid_view = ImageView::new_default(grid_u.clone()).unwrap();
+        let momentum_u = Self::get_grid_image(mgr, width, height);
+        let momentum_view = ImageView::new_default(momentum_u.clone()).unwrap();
         let grid_sampler = Sampler::new(
             mgr.context.device().clone(),
             SamplerCreateInfo {
@@ -67,15 +207,74 @@ impl Simulator {
         )
         .unwrap();
 
-        Simulator {
+        let drive_buffer = Self::get_drive_buffer(mgr, &[], height);
+
+        let mut simulator = Simulator {
             grid_u,
             grid_view,
             grid_sampler,
+            momentum_u,
+            momentum_view,
+            grid_changes: 0,
+            momentum_changes: None,
+            momentum_updated: None,
 
             pipeline,
+            observables_pipeline,
+            detectors_pipeline,
             width,
             height,
-        }
+
+            step_count: 0,
+            time: 0.0,
+
+            pending_commands: None,
+
+            measure_observables: false,
+            pending_observables: VecDeque::new(),
+
+            stability_guard: false,
+            snapshot: None,
+            last_snapshot_step: None,
+            baseline_probability: None,
+            generation: 0,
+            rollback: None,
+
+            max_potential: 0.0,
+            potential_stroke: false,
+
+            drives: Vec::new(),
+            drive_buffer,
+            max_drive_potential: 0.0,
+
+            detectors: Vec::new(),
+            detector_buffer: Self::get_detector_buffer(mgr, &[]),
+            outflow: Vec::new(),
+            detector_generation: 0,
+            pending_detections: VecDeque::new(),
+        };
+        simulator.zero_grid(mgr);
+
+        simulator
+    }
+
+    fn get_pipeline(mgr: &VulkanManager, module: Arc<ShaderModule>) -> Arc<ComputePipeline> {
+        let cs = module.entry_point("main").unwrap();
+        let stage = PipelineShaderStageCreateInfo::new(cs);
+        let layout = PipelineLayout::new(
+            mgr.context.device().clone(),
+            PipelineDescriptorSetLayoutCreateInfo::from_stages([&stage])
+                .into_pipeline_layout_create_info(mgr.context.device().clone())
+                .unwrap(),
+        )
+        .unwrap();
+
+        ComputePipeline::new(
+            mgr.context.device().clone(),
+            None,
+            ComputePipelineCreateInfo::stage_layout(stage, layout),
+        )
+        .unwrap()
     }
 
     pub fn get_grid_image(mgr: &VulkanManager, width: u32, height: u32) -> Arc<Image> {
@@ -85,7 +284,10 @@ impl Simulator {
                 image_type: ImageType::Dim2d,
                 format: Format::R32G32B32A32_SFLOAT,
                 extent: [width, height, 1],
-                usage: ImageUsage::STORAGE | ImageUsage::SAMPLED | ImageUsage::TRANSFER_DST,
+                usage: ImageUsage::STORAGE
+                    | ImageUsage::SAMPLED
+                    | ImageUsage::TRANSFER_SRC
+                    | ImageUsage::TRANSFER_DST,
                 ..Default::default()
             },
             AllocationCreateInfo {
@@ -96,15 +298,213 @@ impl Simulator {
         .unwrap()
     }
 
+    fn get_drive_buffer(
+        mgr: &VulkanManager,
+        drives: &[DrivenPotential],
+        height: u32,
+    ) -> Subbuffer<[[f32; 4]]> {
+        Buffer::from_iter(
+            mgr.memory_allocator.clone(),
+            BufferCreateInfo {
+                usage: BufferUsage::STORAGE_BUFFER,
+                ..Default::default()
+            },
+            AllocationCreateInfo {
+                memory_type_filter: MemoryTypeFilter::PREFER_DEVICE
+                    | MemoryTypeFilter::HOST_SEQUENTIAL_WRITE,
+                ..Default::default()
+            },
+            drive::pack(drives, height),
+        )
+        .unwrap()
+    }
+
+    /// The potentials that change over time.
+    pub fn drives(&self) -> &[DrivenPotential] {
+        &self.drives
+    }
+
+    /// Replaces the potentials that change over time. Their coordinates are in cells, so they
+    /// stay where they are when the grid is resized.
+    pub fn set_drives(&mut self, mgr: &VulkanManager, drives: Vec<DrivenPotential>) {
+        self.drives = drives;
+        self.pack_drives(mgr);
+    }
+
+    fn pack_drives(&mut self, mgr: &VulkanManager) {
+        self.drive_buffer = Self::get_drive_buffer(mgr, &self.drives, self.height);
+        self.max_drive_potential = self
+            .drives
+            .iter()
+            .map(|drive| drive.max_value(self.width, self.height))
+            .sum();
+    }
+
+    fn get_detector_buffer(mgr: &VulkanManager, detectors: &[Detector]) -> Subbuffer<[[f32; 4]]> {
+        Buffer::from_iter(
+            mgr.memory_allocator.clone(),
+            BufferCreateInfo {
+                usage: BufferUsage::STORAGE_BUFFER,
+                ..Default::default()
+            },
+            AllocationCreateInfo {
+                memory_type_filter: MemoryTypeFilter::PREFER_DEVICE
+                    | MemoryTypeFilter::HOST_SEQUENTIAL_WRITE,
+                ..Default::default()
+            },
+            detector::pack(detectors),
+        )
+        .unwrap()
+    }
+
+    /// The regions that are measured after every step.
+    pub fn detectors(&self) -> &[Detector] {
+        &self.detectors
+    }
+
+    /// Replaces the detectors, of which only the first [`MAX_DETECTORS`] are measured, and resets
+    /// their outflow. Readings of the old detectors that have not been taken yet are dropped.
+    pub fn set_detectors(&mut self, mgr: &VulkanManager, mut detectors: Vec<Detector>) {
+        detectors.truncate(MAX_DETECTORS);
+        self.detector_buffer = Self::get_detector_buffer(mgr, &detectors);
+        self.detectors = detectors;
+        self.reset_outflow();
+    }
+
+    /// Starts counting the outflow of every detector from 0 again.
+    pub fn reset_outflow(&mut self) {
+        self.outflow = vec![0.0; self.detectors.len()];
+        self.pending_detections.clear();
+        self.detector_generation += 1;
+    }
+
+    /// Takes the detector readings measured since the last call, oldest first. Like the
+    /// observables, those measured on the GPU only arrive once their submission has finished.
+    pub fn take_detections(&mut self) -> Vec<DetectorSample> {
+        let mut detections = Vec::new();
+        while let Some(pending) = self.pending_detections.front() {
+            let mut samples = match &pending.measurements {
+                DetectorMeasurements::Gpu {
+                    sums,
+                    groups,
+                    times,
+                } => {
+                    // Still locked by a submission that has not finished
+                    let Ok(sums) = sums.read() else {
+                        break;
+                    };
+                    let count = self.detectors.len();
+                    times
+                        .iter()
+                        .enumerate()
+                        .map(|(slot, &time)| {
+                            let slot = &sums[slot * groups * MAX_DETECTORS..];
+                            let readings = (0..count)
+                                .map(|d| {
+                                    let mut reading = Reading::default();
+                                    for group in slot.chunks_exact(MAX_DETECTORS).take(*groups) {
+                                        let [probability, flux, outflow, _] = group[d];
+                                        reading.probability += probability as f64;
+                                        reading.flux += flux as f64;
+                                        reading.outflow += outflow as f64;
+                                    }
+                                    reading
+                                })
+                                .collect();
+                            DetectorSample { time, readings }
+                        })
+                        .collect()
+                }
+                DetectorMeasurements::Cpu(samples) => samples.clone(),
+            };
+            let pending = self.pending_detections.pop_front().unwrap();
+            if pending.generation != self.detector_generation {
+                continue;
+            }
+
+            // The last step of a batch is always measured, so its outflow is the batch's
+            let batch_outflow = samples
+                .last()
+                .map(|sample| {
+                    sample
+                        .readings
+                        .iter()
+                        .map(|reading| reading.outflow)
+                        .collect()
+                })
+                .unwrap_or_else(|| vec![0.0; self.outflow.len()]);
+            for sample in &mut samples {
+                for (reading, total) in sample.readings.iter_mut().zip(&self.outflow) {
+                    reading.outflow += total;
+                }
+            }
+            for (total, outflow) in self.outflow.iter_mut().zip(batch_outflow) {
+                *total += outflow;
+            }
+            detections.extend(samples);
+        }
+
+        detections
+    }
+
+    /// Changes the number of cells in the grid, resampling the current state onto the new grid.
+    /// Like any replacement of the grid this follows the rule in [`Self::write_grid`].
     pub fn resize(&mut self, mgr: &VulkanManager, width: u32, height: u32) {
-        self.width = width;
-        self.height = height;
-        self.grid_u = Self::get_grid_image(mgr, width, height);
-        self.grid_view = ImageView::new_default(self.grid_u.clone()).unwrap();
-        self.zero_grid(mgr);
+        if width == self.width && height == self.height {
+            return;
+        }
+
+        let grid = self.read_grid(mgr).resample(width, height);
+        self.write_grid(mgr, &grid);
+    }
+
+    /// The number of time steps taken since the simulation was last reset.
+    pub fn step_count(&self) -> u64 {
+        self.step_count
+    }
+
+    /// The simulation time elapsed since the simulation was last reset, which is the sum of the
+    /// time steps taken.
+    pub fn time(&self) -> f64 {
+        self.time
+    }
+
+    /// The largest `|V|` on the grid, which limits the time step of the leapfrog integrator, see
+    /// [`StepParams::stability_limit`]. While drawing on the potential or with any drives this is
+    /// an upper bound.
+    pub fn max_potential(&self) -> f32 {
+        self.max_potential + self.max_drive_potential
+    }
+
+    /// Resets the simulation clock, for callers that start a new simulation.
+    pub fn reset_clock(&mut self) {
+        self.set_clock(0, 0.0);
+    }
+
+    /// Sets the simulation clock, for callers that continue a saved simulation. The snapshots of
+    /// the stability guard belong to the old clock, so they are dropped too.
+    pub fn set_clock(&mut self, step_count: u64, time: f64) {
+        self.step_count = step_count;
+        self.time = time;
+        self.forget_history();
     }
 
-    pub fn zero_grid(&self, mgr: &VulkanManager) {
+    /// Drops the snapshot and the probability that the stability guard compares against, after
+    /// the wave function has been changed by hand.
+    fn forget_history(&mut self) {
+        self.snapshot = None;
+        self.last_snapshot_step = None;
+        self.baseline_probability = None;
+        self.generation += 1;
+    }
+
+    /// Clears the wave function and potential. Like any replacement of the grid this follows the
+    /// rule in [`Self::write_grid`].
+    pub fn zero_grid(&mut self, mgr: &VulkanManager) {
+        self.forget_history();
+        self.grid_changes += 1;
+        self.max_potential = 0.0;
+
         let mut builder = mgr.get_compute_cmdbuffer_builder();
 
         builder
@@ -117,71 +517,792 @@ impl Simulator {
         mgr.execute_compute_cmdbuffer_from_builder(builder);
     }
 
-    pub fn compute(&self, mgr: &VulkanManager, ui_state: &UIState) {
-        let layout = self.pipeline.layout().set_layouts().get(0).unwrap();
+    /// Copies the grid image back to the host.
+    pub fn read_grid(&self, mgr: &VulkanManager) -> Grid {
+        let buffer = Buffer::new_slice::<[f32; 4]>(
+            mgr.memory_allocator.clone(),
+            BufferCreateInfo {
+                usage: BufferUsage::TRANSFER_DST,
+                ..Default::default()
+            },
+            AllocationCreateInfo {
+                memory_type_filter: MemoryTypeFilter::PREFER_HOST
+                    | MemoryTypeFilter::HOST_RANDOM_ACCESS,
+                ..Default::default()
+            },
+            (self.width * self.height) as u64,
+        )
+        .unwrap();
+
+        let mut builder = mgr.get_compute_cmdbuffer_builder();
+        builder
+            .copy_image_to_buffer(CopyImageToBufferInfo::image_buffer(
+                self.grid_u.clone(),
+                buffer.clone(),
+            ))
+            .unwrap();
+        mgr.execute_compute_cmdbuffer_from_builder(builder);
+
+        Grid {
+            width: self.width,
+            height: self.height,
+            cells: buffer.read().unwrap().to_vec(),
+        }
+    }
+
+    /// Uploads `grid` to the grid image, reallocating the image if the dimensions differ.
+    ///
+    /// Every replacement of the grid from outside of the simulation, here, in [`Self::resize`]
+    /// and in [`Self::zero_grid`], follows the same rule: the stability guard starts over, the
+    /// largest potential is recomputed and the Momentum layer is marked out of date, but the
+    /// clock and the drives are left as they are. Callers that start or continue another
+    /// simulation set them with [`Self::reset_clock`] or [`Self::set_clock`] and
+    /// [`Self::set_drives`].
+    pub fn write_grid(&mut self, mgr: &VulkanManager, grid: &Grid) {
+        self.forget_history();
+        self.store_grid(mgr, grid);
+    }
+
+    /// Uploads `grid` without touching the stability guard, for grids that the simulation has
+    /// stepped to itself.
+    fn store_grid(&mut self, mgr: &VulkanManager, grid: &Grid) {
+        if grid.width != self.width || grid.height != self.height {
+            self.width = grid.width;
+            self.height = grid.height;
+            self.grid_u = Self::get_grid_image(mgr, grid.width, grid.height);
+            self.grid_view = ImageView::new_default(self.grid_u.clone()).unwrap();
+            self.momentum_u = Self::get_grid_image(mgr, grid.width, grid.height);
+            self.momentum_view = ImageView::new_default(self.momentum_u.clone()).unwrap();
+            self.pack_drives(mgr);
+        }
+        self.update_max_potential(grid);
+        self.grid_changes += 1;
+
+        Self::upload(mgr, &self.grid_u, grid);
+    }
+
+    fn update_max_potential(&mut self, grid: &Grid) {
+        self.max_potential = grid
+            .cells
+            .iter()
+            .map(|cell| cell[2].abs())
+            .fold(0.0, f32::max);
+    }
+
+    /// Called when the mouse button is released. After drawing on the potential the largest `|V|`
+    /// is read back from the grid, since the bound kept during the stroke can be far too high.
+    pub fn end_stroke(&mut self, mgr: &VulkanManager) {
+        if !self.potential_stroke {
+            return;
+        }
+        self.potential_stroke = false;
+        if let Some(commands) = self.pending_commands.take() {
+            mgr.execute_compute_cmdbuffer(commands);
+        }
+        let grid = self.read_grid(mgr);
+        self.update_max_potential(&grid);
+    }
+
+    /// Transforms the wave function to momentum space for the Momentum layer, if the grid has
+    /// changed since the last time and [`MOMENTUM_INTERVAL`] has passed, so the layer lags behind
+    /// a running simulation a little and catches up once it is paused. The steps recorded for
+    /// this frame are run first, so that the layer shows the same wave function as the others.
+    pub fn update_momentum(&mut self, mgr: &VulkanManager) {
+        if self.momentum_changes == Some(self.grid_changes)
+            || self
+                .momentum_updated
+                .is_some_and(|updated| updated.elapsed() < MOMENTUM_INTERVAL)
+        {
+            return;
+        }
+        if let Some(commands) = self.pending_commands.take() {
+            mgr.execute_compute_cmdbuffer(commands);
+        }
+        Self::upload(
+            mgr,
+            &self.momentum_u,
+            &momentum::momentum_grid(&self.read_grid(mgr)),
+        );
+        self.momentum_changes = Some(self.grid_changes);
+        self.momentum_updated = Some(Instant::now());
+    }
+
+    /// Copies `grid` to `image`, which must be the same size.
+    fn upload(mgr: &VulkanManager, image: &Arc<Image>, grid: &Grid) {
+        let buffer = Buffer::from_iter(
+            mgr.memory_allocator.clone(),
+            BufferCreateInfo {
+                usage: BufferUsage::TRANSFER_SRC,
+                ..Default::default()
+            },
+            AllocationCreateInfo {
+                memory_type_filter: MemoryTypeFilter::PREFER_HOST
+                    | MemoryTypeFilter::HOST_SEQUENTIAL_WRITE,
+                ..Default::default()
+            },
+            grid.cells.iter().copied(),
+        )
+        .unwrap();
+
+        let mut builder = mgr.get_compute_cmdbuffer_builder();
+        builder
+            .copy_buffer_to_image(CopyBufferToImageInfo::buffer_image(buffer, image.clone()))
+            .unwrap();
+        mgr.execute_compute_cmdbuffer_from_builder(builder);
+    }
+
+    /// Records the next frame of the simulation, applying the brush from `ui_state` if it is held
+    /// down. The commands are submitted by the renderer along with the frame, see
+    /// [`Self::take_commands`].
+    pub fn compute(&mut self, mgr: &VulkanManager, ui_state: &UIState) {
+        if let Some(commands) = self.pending_commands.take() {
+            mgr.execute_compute_cmdbuffer(commands);
+        }
+
+        let params = ui_state.step_params();
+        if ui_state.brush_enabled == 1 && ui_state.brush_layer == SimulationLayer::Potential {
+            self.potential_stroke = true;
+            // The brush weighs its value by at most 1, so this is as far as it can move any cell
+            let value = ui_state.brush_value.abs();
+            match ui_state.brush_mode {
+                BrushMode::Set => self.max_potential = self.max_potential.max(value),
+                BrushMode::Add | BrushMode::Subtract => self.max_potential += value,
+                BrushMode::Erase | BrushMode::Measure => {}
+            }
+        }
+        if params.integrator != Integrator::Leapfrog && ui_state.brush_enabled != 1 {
+            self.advance_on_cpu(mgr, &params, ui_state.steps_per_frame.max(1));
+            return;
+        }
+
+        // The brush only draws, so repeating it would just draw the same stroke again
+        let steps = if ui_state.brush_enabled == 1 {
+            1
+        } else {
+            ui_state.steps_per_frame.max(1)
+        };
+
+        let builder = self.record(
+            mgr,
+            &params,
+            cs::PushConstantData {
+                time_step: params.time_step,
+                speed: ui_state.speed,
+                theta: ui_state.theta,
+                brush_x: ui_state.brush_x,
+                brush_y: ui_state.brush_y,
+                brush_enabled: ui_state.brush_enabled,
+                brush_radius: ui_state.brush_radius,
+                brush_value: ui_state.brush_value,
+                brush_layer: ui_state.brush_layer as i32,
+                brush_mode: ui_state.brush_mode as i32,
+                boundary_condition: params.boundary_condition as i32,
+                absorbing_width: params.absorbing_width,
+                absorbing_strength: params.absorbing_strength,
+                cell_spacing: params.cell_spacing,
+                hbar: params.hbar,
+                mass: params.mass,
+                stage: 0,
+                time: 0.0,
+                drive_count: self.drives.len() as i32,
+            },
+            steps,
+        );
+        self.pending_commands = Some(builder.build().unwrap());
+    }
+
+    /// Takes the commands recorded by [`Self::compute`] so that they can be submitted ahead of the
+    /// frame that draws their result.
+    pub fn take_commands(&mut self) -> Option<Arc<PrimaryAutoCommandBuffer>> {
+        self.pending_commands.take()
+    }
+
+    /// Advances the simulation by one step without any brush input.
+    pub fn step(&mut self, mgr: &VulkanManager, params: &StepParams) {
+        self.advance(mgr, params, 1);
+    }
+
+    /// Advances the simulation by `steps` steps without any brush input, in a single submission
+    /// that is waited on.
+    pub fn advance(&mut self, mgr: &VulkanManager, params: &StepParams, steps: u32) {
+        if params.integrator != Integrator::Leapfrog {
+            self.advance_on_cpu(mgr, params, steps);
+            return;
+        }
+
+        let builder = self.record(
+            mgr,
+            params,
+            cs::PushConstantData {
+                time_step: params.time_step,
+                speed: 0.0,
+                theta: 0.0,
+                brush_x: 0,
+                brush_y: 0,
+                brush_enabled: 0,
+                brush_radius: 0.0,
+                brush_value: 0.0,
+                brush_layer: 0,
+                brush_mode: 0,
+                boundary_condition: params.boundary_condition as i32,
+                absorbing_width: params.absorbing_width,
+                absorbing_strength: params.absorbing_strength,
+                cell_spacing: params.cell_spacing,
+                hbar: params.hbar,
+                mass: params.mass,
+                stage: 0,
+                time: 0.0,
+                drive_count: self.drives.len() as i32,
+            },
+            steps,
+        );
+        mgr.execute_compute_cmdbuffer_from_builder(builder);
+    }
+
+    /// The Crank–Nicolson and split-step integrators work on whole rows and columns at once, with
+    /// tridiagonal solves and Fourier transforms, so they run on the CPU with the grid copied back
+    /// and forth.
+    fn advance_on_cpu(&mut self, mgr: &VulkanManager, params: &StepParams, steps: u32) {
+        let mut solver = DrivenSolver::wrap(
+            solver::cpu_solver(params.integrator, self.read_grid(mgr)),
+            &self.drives,
+            self.time,
+        );
+        let mut observables = Vec::new();
+        let mut detections = Vec::new();
+        let mut outflow = vec![0.0; self.detectors.len()];
+        for step in 1..=steps {
+            solver.step(params);
+
+            let time = self.time + params.time_step as f64 * step as f64;
+            if self.measure_observables && step + MAX_MEASURED_STEPS > steps {
+                let mut grid = solver.read_grid();
+                drive::add_potentials(&mut grid, &self.drives, time);
+                observables.push(Observables::from_grid(&grid, time, params));
+            }
+
+            if !self.detectors.is_empty() {
+                let mut readings = detector::measure(&solver.read_grid(), &self.detectors, params);
+                for (reading, outflow) in readings.iter_mut().zip(&mut outflow) {
+                    *outflow += reading.flux * params.time_step as f64;
+                    reading.outflow = *outflow;
+                }
+                if step + MAX_MEASURED_STEPS > steps {
+                    detections.push(DetectorSample { time, readings });
+                }
+            }
+        }
+        if !detections.is_empty() {
+            self.pending_detections.push_back(PendingDetections {
+                measurements: DetectorMeasurements::Cpu(detections),
+                generation: self.detector_generation,
+            });
+        }
+        self.store_grid(mgr, &solver.read_grid());
+        self.advance_clock(params.time_step, steps);
+
+        if !observables.is_empty() {
+            let snapshot = self.snapshot_due().then(|| {
+                let mut builder = mgr.get_compute_cmdbuffer_builder();
+                let snapshot = self.take_snapshot(mgr, &mut builder);
+                mgr.execute_compute_cmdbuffer_from_builder(builder);
+                snapshot
+            });
+            self.pending_observables.push_back(PendingObservables {
+                measurements: Measurements::Cpu(observables),
+                generation: self.generation,
+                snapshot,
+            });
+        }
+    }
+
+    /// Takes the observables measured since the last call, oldest first. Those measured on the
+    /// GPU only arrive once the submission that measured them has finished, which is usually a
+    /// frame or two later.
+    ///
+    /// With the [`Self::stability_guard`] on, the measurements are also checked for instability.
+    /// If there is any the grid is rolled back to the last snapshot, everything measured after
+    /// the instability is dropped, and [`Self::take_rollback`] reports what happened.
+    pub fn take_observables(&mut self, mgr: &VulkanManager) -> Vec<Observables> {
+        let mut observables = Vec::new();
+        while let Some(pending) = self.pending_observables.front() {
+            let measured = match &pending.measurements {
+                Measurements::Gpu {
+                    sums,
+                    groups,
+                    times,
+                    params,
+                } => {
+                    // Still locked by a submission that has not finished
+                    let Ok(sums) = sums.read() else {
+                        break;
+                    };
+                    times
+                        .iter()
+                        .enumerate()
+                        .map(|(slot, &time)| {
+                            let sums = &sums[slot * groups * 2..(slot + 1) * groups * 2];
+                            Observables::from_partial_sums(sums, time, params)
+                        })
+                        .collect()
+                }
+                Measurements::Cpu(measured) => measured.clone(),
+            };
+            let pending = self.pending_observables.pop_front().unwrap();
+
+            if self.stability_guard && pending.generation == self.generation {
+                if let Some((i, instability)) = self.find_instability(&measured) {
+                    observables.extend_from_slice(&measured[..=i]);
+                    self.roll_back(mgr, instability, measured[i].time);
+                    break;
+                }
+                if let Some(snapshot) = pending.snapshot {
+                    self.snapshot = Some(snapshot);
+                }
+            }
+            observables.extend(measured);
+        }
+
+        observables
+    }
+
+    /// Takes the last rollback made by the stability guard, if there was one since the last call.
+    pub fn take_rollback(&mut self) -> Option<Rollback> {
+        self.rollback.take()
+    }
+
+    /// The first measurement that shows any instability, along with its index.
+    fn find_instability(&mut self, measured: &[Observables]) -> Option<(usize, Instability)> {
+        for (i, sample) in measured.iter().enumerate() {
+            let baseline = self
+                .baseline_probability
+                .unwrap_or(sample.total_probability);
+            if let Some(instability) = sample.instability(baseline) {
+                return Some((i, instability));
+            }
+            self.baseline_probability = Some(baseline);
+        }
+        None
+    }
+
+    /// Restores the last snapshot, if there is one, and drops everything measured since.
+    fn roll_back(&mut self, mgr: &VulkanManager, instability: Instability, time: f64) {
+        self.pending_observables.clear();
+
+        let restored_time = self.snapshot.as_ref().map(|snapshot| {
+            let mut builder = mgr.get_compute_cmdbuffer_builder();
+            builder
+                .copy_image(CopyImageInfo::images(
+                    snapshot.image.clone(),
+                    self.grid_u.clone(),
+                ))
+                .unwrap();
+            mgr.execute_compute_cmdbuffer_from_builder(builder);
+
+            self.step_count = snapshot.step_count;
+            self.time = snapshot.time;
+            self.grid_changes += 1;
+            self.last_snapshot_step = Some(snapshot.step_count);
+            snapshot.time
+        });
+
+        self.rollback = Some(Rollback {
+            instability,
+            time,
+            restored_time,
+        });
+    }
+
+    /// Whether the guard is on and enough steps have passed since the last snapshot to take
+    /// another.
+    fn snapshot_due(&self) -> bool {
+        self.stability_guard
+            && self
+                .last_snapshot_step
+                .is_none_or(|step| self.step_count >= step + SNAPSHOT_INTERVAL)
+    }
+
+    /// Records a copy of the grid as it is at the end of `builder`.
+    fn take_snapshot(
+        &mut self,
+        mgr: &VulkanManager,
+        builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+    ) -> Snapshot {
+        let image = Self::get_grid_image(mgr, self.width, self.height);
+        builder
+            .copy_image(CopyImageInfo::images(self.grid_u.clone(), image.clone()))
+            .unwrap();
+
+        self.last_snapshot_step = Some(self.step_count);
+        Snapshot {
+            image,
+            step_count: self.step_count,
+            time: self.time,
+        }
+    }
+
+    /// Advances an eigenstate search by `iterations` steps of imaginary time in place of the
+    /// simulation, and shows its current guess, or the last state found once it has finished.
+    /// Imaginary time does not advance the simulation clock.
+    pub fn relax(
+        &mut self,
+        mgr: &VulkanManager,
+        search: &mut EigenstateSearch,
+        params: &StepParams,
+        iterations: u32,
+    ) {
+        search.iterate(params, iterations);
+        match search.states.last() {
+            Some(state) if search.is_finished() => self.write_grid(mgr, &state.grid),
+            _ => self.write_grid(mgr, &search.candidate()),
+        }
+    }
+
+    fn advance_clock(&mut self, time_step: f32, steps: u32) {
+        self.step_count += steps as u64;
+        self.time += time_step as f64 * steps as f64;
+    }
+
+    /// Records `steps` pairs of leapfrog stages into one command buffer. Every dispatch reads and
+    /// writes the grid image, so the builder places a barrier between each of them. While the
+    /// brush is held down the kernel only draws, in a single dispatch, so the clock only advances
+    /// when it is not.
+    ///
+    /// If [`Self::measure_observables`] is set, the last steps are each followed by a reduction
+    /// over the grid, see [`Self::take_observables`].
+    fn record(
+        &mut self,
+        mgr: &VulkanManager,
+        params: &StepParams,
+        mut push_constants: cs::PushConstantData,
+        steps: u32,
+    ) -> AutoCommandBufferBuilder<PrimaryAutoCommandBuffer> {
+        let start_time = self.time;
+        self.grid_changes += 1;
+        let stepping = push_constants.brush_enabled == 0;
+        if stepping {
+            self.advance_clock(push_constants.time_step, steps);
+        } else {
+            self.forget_history();
+        }
+
+        let groups = [
+            self.width.div_ceil(TILE_SIZE),
+            self.height.div_ceil(TILE_SIZE),
+            1,
+        ];
+
+        let layout = self.pipeline.layout().set_layouts().first().unwrap();
         let set = DescriptorSet::new(
             mgr.descriptor_set_allocator.clone(),
             layout.clone(),
-            [WriteDescriptorSet::image_view(0, self.grid_view.clone())], // 0 is the binding
+            [
+                WriteDescriptorSet::image_view(0, self.grid_view.clone()), // 0 is the binding
+                WriteDescriptorSet::buffer(1, self.drive_buffer.clone()),
+            ],
             [],
         )
         .unwrap();
 
-        let mut stage0_builder = mgr.get_compute_cmdbuffer_builder();
-        let mut stage1_builder = mgr.get_compute_cmdbuffer_builder();
-
-        let mut push_constants = cs::PushConstantData {
-            time_step: ui_state.time_step,
-            speed: ui_state.speed,
-            theta: ui_state.theta,
-            brush_x: ui_state.brush_x,
-            brush_y: ui_state.brush_y,
-            brush_enabled: ui_state.brush_enabled,
-            brush_radius: ui_state.brush_radius,
-            brush_value: ui_state.brush_value,
-            brush_layer: ui_state.brush_layer as i32,
-            boundary_condition: ui_state.boundary_condition as i32,
-            stage: 0,
+        let measured = if self.measure_observables && stepping {
+            steps.min(MAX_MEASURED_STEPS)
+        } else {
+            0
         };
+        let group_count = (groups[0] * groups[1]) as usize;
+        let observables = (measured > 0).then(|| {
+            let sums = Buffer::new_slice::<[f32; 4]>(
+                mgr.memory_allocator.clone(),
+                BufferCreateInfo {
+                    usage: BufferUsage::STORAGE_BUFFER,
+                    ..Default::default()
+                },
+                AllocationCreateInfo {
+                    memory_type_filter: MemoryTypeFilter::PREFER_HOST
+                        | MemoryTypeFilter::HOST_RANDOM_ACCESS,
+                    ..Default::default()
+                },
+                (measured as usize * group_count * 2) as u64,
+            )
+            .unwrap();
 
-        unsafe {
-            stage0_builder
-                .bind_pipeline_compute(self.pipeline.clone())
-                .unwrap()
-                .bind_descriptor_sets(
-                    PipelineBindPoint::Compute,
-                    self.pipeline.layout().clone(),
-                    0,
-                    set.clone(),
-                )
-                .unwrap()
-                .push_constants(self.pipeline.layout().clone(), 0, push_constants)
-                .unwrap()
-                .dispatch([self.width, self.height, 1])
+            let layout = self
+                .observables_pipeline
+                .layout()
+                .set_layouts()
+                .first()
                 .unwrap();
-        }
+            let set = DescriptorSet::new(
+                mgr.descriptor_set_allocator.clone(),
+                layout.clone(),
+                [
+                    WriteDescriptorSet::image_view(0, self.grid_view.clone()),
+                    WriteDescriptorSet::buffer(1, sums.clone()),
+                    WriteDescriptorSet::buffer(2, self.drive_buffer.clone()),
+                ],
+                [],
+            )
+            .unwrap();
+
+            (sums, set)
+        });
+
+        // Unlike the observables, every step is measured to add up the outflow, but only the
+        // readings of the last ones are kept
+        let detections = (stepping && !self.detectors.is_empty()).then(|| {
+            let detected = steps.min(MAX_MEASURED_STEPS) as usize;
+            let sums = Buffer::from_iter(
+                mgr.memory_allocator.clone(),
+                BufferCreateInfo {
+                    usage: BufferUsage::STORAGE_BUFFER,
+                    ..Default::default()
+                },
+                AllocationCreateInfo {
+                    memory_type_filter: MemoryTypeFilter::PREFER_HOST
+                        | MemoryTypeFilter::HOST_RANDOM_ACCESS,
+                    ..Default::default()
+                },
+                vec![[0.0; 4]; (detected + 1) * group_count * MAX_DETECTORS],
+            )
+            .unwrap();
 
-        push_constants.stage = 1;
-
-        unsafe {
-            stage1_builder
-                .bind_pipeline_compute(self.pipeline.clone())
-                .unwrap()
-                .bind_descriptor_sets(
-                    PipelineBindPoint::Compute,
-                    self.pipeline.layout().clone(),
-                    0,
-                    set.clone(),
-                )
-                .unwrap()
-                .push_constants(self.pipeline.layout().clone(), 0, push_constants)
-                .unwrap()
-                .dispatch([self.width, self.height, 1])
+            let layout = self
+                .detectors_pipeline
+                .layout()
+                .set_layouts()
+                .first()
                 .unwrap();
+            let set = DescriptorSet::new(
+                mgr.descriptor_set_allocator.clone(),
+                layout.clone(),
+                [
+                    WriteDescriptorSet::image_view(0, self.grid_view.clone()),
+                    WriteDescriptorSet::buffer(1, self.detector_buffer.clone()),
+                    WriteDescriptorSet::buffer(2, sums.clone()),
+                ],
+                [],
+            )
+            .unwrap();
+
+            (sums, set, detected as u32)
+        });
+
+        let mut builder = mgr.get_compute_cmdbuffer_builder();
+        for step in 0..steps {
+            // Measuring binds the other pipelines, so the leapfrog one has to be bound again
+            if step == 0 || observables.is_some() || detections.is_some() {
+                builder
+                    .bind_pipeline_compute(self.pipeline.clone())
+                    .unwrap()
+                    .bind_descriptor_sets(
+                        PipelineBindPoint::Compute,
+                        self.pipeline.layout().clone(),
+                        0,
+                        set.clone(),
+                    )
+                    .unwrap();
+            }
+
+            let step_start = start_time + push_constants.time_step as f64 * step as f64;
+            push_constants.time = (step_start + push_constants.time_step as f64 / 2.0) as f32;
+            // The brush ignores the stage, so it only gets one dispatch to draw once per frame
+            let stages = if stepping { 2 } else { 1 };
+            for stage in 0..stages {
+                push_constants.stage = stage;
+                unsafe {
+                    builder
+                        .push_constants(self.pipeline.layout().clone(), 0, push_constants)
+                        .unwrap()
+                        .dispatch(groups)
+                        .unwrap();
+                }
+            }
+
+            if let Some((_, observables_set)) = &observables
+                && step + measured >= steps
+            {
+                let layout = self.observables_pipeline.layout().clone();
+                unsafe {
+                    builder
+                        .bind_pipeline_compute(self.observables_pipeline.clone())
+                        .unwrap()
+                        .bind_descriptor_sets(
+                            PipelineBindPoint::Compute,
+                            layout.clone(),
+                            0,
+                            observables_set.clone(),
+                        )
+                        .unwrap()
+                        .push_constants(
+                            layout,
+                            0,
+                            observables_cs::ObservablesPushConstants {
+                                boundary_condition: push_constants.boundary_condition,
+                                slot: (step + measured - steps) as i32,
+                                time: (step_start + push_constants.time_step as f64) as f32,
+                                drive_count: push_constants.drive_count,
+                            },
+                        )
+                        .unwrap()
+                        .dispatch(groups)
+                        .unwrap();
+                }
+            }
+
+            if let Some((_, detectors_set, detected)) = &detections {
+                let layout = self.detectors_pipeline.layout().clone();
+                unsafe {
+                    builder
+                        .bind_pipeline_compute(self.detectors_pipeline.clone())
+                        .unwrap()
+                        .bind_descriptor_sets(
+                            PipelineBindPoint::Compute,
+                            layout.clone(),
+                            0,
+                            detectors_set.clone(),
+                        )
+                        .unwrap()
+                        .push_constants(
+                            layout,
+                            0,
+                            detectors_cs::DetectorPushConstants {
+                                boundary_condition: push_constants.boundary_condition,
+                                detector_count: self.detectors.len() as i32,
+                                slot: if step + detected >= steps {
+                                    (step + detected - steps) as i32
+                                } else {
+                                    -1
+                                },
+                                measured: *detected as i32,
+                                time_step: push_constants.time_step,
+                                coupling: params.hbar
+                                    / (params.mass * params.cell_spacing * params.cell_spacing),
+                            },
+                        )
+                        .unwrap()
+                        .dispatch(groups)
+                        .unwrap();
+                }
+            }
+        }
+
+        if let Some((sums, _, detected)) = detections {
+            let times = (steps - detected + 1..=steps)
+                .map(|step| start_time + push_constants.time_step as f64 * step as f64)
+                .collect();
+            self.pending_detections.push_back(PendingDetections {
+                measurements: DetectorMeasurements::Gpu {
+                    sums,
+                    groups: group_count,
+                    times,
+                },
+                generation: self.detector_generation,
+            });
         }
 
-        mgr.execute_compute_cmdbuffer_from_builder(stage0_builder);
-        mgr.execute_compute_cmdbuffer_from_builder(stage1_builder);
+        if let Some((sums, _)) = observables {
+            let times = (steps - measured + 1..=steps)
+                .map(|step| start_time + push_constants.time_step as f64 * step as f64)
+                .collect();
+            let snapshot = self
+                .snapshot_due()
+                .then(|| self.take_snapshot(mgr, &mut builder));
+            self.pending_observables.push_back(PendingObservables {
+                measurements: Measurements::Gpu {
+                    sums,
+                    groups: group_count,
+                    times,
+                    params: *params,
+                },
+                generation: self.generation,
+                snapshot,
+            });
+        }
+
+        builder
+    }
+}
+
+/// Drives a [`Simulator`] through the [`Solver`] trait using the manager it was created with.
+pub struct GpuSolver<'a> {
+    mgr: &'a VulkanManager,
+    simulator: &'a mut Simulator,
+}
+
+impl<'a> GpuSolver<'a> {
+    pub fn new(mgr: &'a VulkanManager, simulator: &'a mut Simulator) -> Self {
+        GpuSolver { mgr, simulator }
+    }
+}
+
+impl Solver for GpuSolver<'_> {
+    fn step(&mut self, params: &StepParams) {
+        self.simulator.step(self.mgr, params);
+    }
+
+    fn read_grid(&self) -> Grid {
+        self.simulator.read_grid(self.mgr)
+    }
+
+    fn write_grid(&mut self, grid: &Grid) {
+        self.simulator.write_grid(self.mgr, grid);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cpu_solver::CpuSolver;
+    use crate::ui_state::BoundaryCondition;
+    use crate::wave_packet::WavePacket;
+
+    /// The GPU kernel against the CPU port, over a potential that is non-zero wherever Ψ is, on
+    /// a grid that leaves the last workgroups hanging over both edges.
+    #[test]
+    #[ignore = "needs a Vulkan device"]
+    fn matches_cpu_solver() {
+        let mgr = VulkanManager::headless();
+        for boundary_condition in [
+            BoundaryCondition::Dirichlet,
+            BoundaryCondition::Neumann,
+            BoundaryCondition::Periodic,
+            BoundaryCondition::Absorbing,
+        ] {
+            let params = StepParams {
+                time_step: 0.1,
+                boundary_condition,
+                absorbing_width: 8.0,
+                ..Default::default()
+            };
+            let mut grid = Grid::new(40, 24);
+            for (i, cell) in grid.cells.iter_mut().enumerate() {
+                let x = (i % 40) as f32 - 20.0;
+                cell[2] = 0.5 + 0.01 * x * x;
+            }
+            WavePacket {
+                x: 18.0,
+                y: 12.0,
+                sigma: 2.0,
+                amplitude: 1.0,
+                theta: 0.5,
+                speed: 0.4,
+            }
+            .apply(&mut grid, &params);
+
+            let mut simulator = Simulator::new(&mgr, grid.width, grid.height);
+            let mut gpu = GpuSolver::new(&mgr, &mut simulator);
+            let mut cpu = CpuSolver::new(grid);
+            for step in 0..10 {
+                let difference = solver::compare_step(&mut cpu, &mut gpu, &params);
+                assert!(
+                    difference < 1e-5,
+                    "{:?}, step {}: {}",
+                    boundary_condition,
+                    step,
+                    difference
+                );
+            }
+        }
     }
 }
 
@@ -191,3 +1312,17 @@ mod cs {
         path: "shaders/compute.glsl"
     }
 }
+
+mod detectors_cs {
+    vulkano_shaders::shader! {
+        ty: "compute",
+        path: "shaders/detectors.glsl"
+    }
+}
+
+mod observables_cs {
+    vulkano_shaders::shader! {
+        ty: "compute",
+        path: "shaders/observables.glsl"
+    }
+}
@@ -0,0 +1,120 @@
+use crate::cpu_solver::CpuSolver;
+use crate::crank_nicolson::CrankNicolsonSolver;
+use crate::grid::Grid;
+use crate::split_step::SplitStepSolver;
+use crate::ui_state::{BoundaryCondition, Integrator, Units};
+
+/// The parameters that control a single time step, independent of any brush input.
+#[derive(Debug, Copy, Clone)]
+pub struct StepParams {
+    pub time_step: f32,
+    pub boundary_condition: BoundaryCondition,
+    /// How many cells from each edge the absorbing layer reaches into the domain.
+    pub absorbing_width: f32,
+    /// The largest value of the complex absorbing potential, reached at the edges.
+    pub absorbing_strength: f32,
+    /// The physical distance between neighbouring cells.
+    pub cell_spacing: f32,
+    pub integrator: Integrator,
+    /// The reduced Planck constant.
+    pub hbar: f32,
+    /// The mass of the particle.
+    pub mass: f32,
+    /// The units that the parameters are given in, which only affects how they are labelled.
+    pub units: Units,
+}
+
+impl Default for StepParams {
+    fn default() -> Self {
+        StepParams {
+            time_step: 0.18,
+            boundary_condition: BoundaryCondition::Neumann,
+            absorbing_width: 24.0,
+            absorbing_strength: 0.5,
+            cell_spacing: 1.0,
+            integrator: Integrator::Leapfrog,
+            hbar: 1.0,
+            mass: 1.0,
+            units: Units::Natural,
+        }
+    }
+}
+
+impl StepParams {
+    /// `ħ/2m`, the factor in front of the Laplacian in `∂Ψ/∂t = i(ħ/2m)∇²Ψ - (i/ħ)VΨ`.
+    pub fn kinetic_coefficient(&self) -> f32 {
+        self.hbar / (2.0 * self.mass)
+    }
+
+    /// The wave number of a particle moving at `speed`, `k = m·v/ħ`.
+    pub fn wave_number(&self, speed: f32) -> f32 {
+        self.mass * speed / self.hbar
+    }
+
+    /// The speed at which a packet's wave number is half of the highest one the grid can hold,
+    /// `π/2dx`. Faster packets are badly distorted by the 5-point stencil.
+    pub fn max_speed(&self) -> f32 {
+        std::f32::consts::FRAC_PI_2 * self.hbar / (self.mass * self.cell_spacing)
+    }
+
+    /// The largest time step at which the leapfrog integrator is stable, given the largest
+    /// `|V|` on the grid. The scheme is stable as long as `dt·|E| ≤ 2ħ` for every energy the grid
+    /// can hold, which are at most `4ħ²/(m·dx²) + max|V|`. The other integrators are stable at any
+    /// time step.
+    pub fn stability_limit(&self, max_potential: f32) -> f32 {
+        let max_energy = 4.0 * self.hbar * self.hbar / (self.mass * self.cell_spacing.powi(2))
+            + max_potential.abs();
+        2.0 * self.hbar / max_energy
+    }
+}
+
+/// A backend that can advance the simulation grid through time.
+///
+/// All backends share the grid layout described in [`Grid`], so the state of one can be handed
+/// to another with [`Solver::read_grid`] and [`Solver::write_grid`].
+pub trait Solver {
+    /// Advances the grid by one time step.
+    fn step(&mut self, params: &StepParams);
+
+    /// Copies the current state of the grid to the host.
+    fn read_grid(&self) -> Grid;
+
+    /// Replaces the state of the grid, resizing it if needed.
+    fn write_grid(&mut self, grid: &Grid);
+}
+
+/// Creates the CPU implementation of `integrator`, starting from `grid`.
+pub fn cpu_solver(integrator: Integrator, grid: Grid) -> Box<dyn Solver> {
+    match integrator {
+        Integrator::Leapfrog => Box::new(CpuSolver::new(grid)),
+        Integrator::CrankNicolson => Box::new(CrankNicolsonSolver::new(grid)),
+        Integrator::SplitStep => Box::new(SplitStepSolver::new(grid)),
+    }
+}
+
+/// Copies the state of `reference` into `candidate`, advances both by one step and returns the
+/// largest difference between the two resulting grids.
+pub fn compare_step(
+    reference: &mut dyn Solver,
+    candidate: &mut dyn Solver,
+    params: &StepParams,
+) -> f32 {
+    candidate.write_grid(&reference.read_grid());
+
+    reference.step(params);
+    candidate.step(params);
+
+    reference.read_grid().max_difference(&candidate.read_grid())
+}
+
+/// The complex absorbing potential at the cell `(x, y)` of a `width` by `height` grid, ramping up
+/// quadratically towards the edges like `absorption` in `compute.glsl`.
+pub fn absorption(x: u32, y: u32, width: u32, height: u32, params: &StepParams) -> f32 {
+    if params.boundary_condition != BoundaryCondition::Absorbing || params.absorbing_width <= 0.0 {
+        return 0.0;
+    }
+
+    let edge_distance = x.min(y).min(width - 1 - x).min(height - 1 - y);
+    let depth = (1.0 - edge_distance as f32 / params.absorbing_width).max(0.0);
+    params.absorbing_strength * depth * depth
+}
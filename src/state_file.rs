@@ -0,0 +1,205 @@
+use std::fs;
+use std::io::{self, Error, ErrorKind};
+use std::path::Path;
+
+use crate::grid::Grid;
+use crate::solver::StepParams;
+use crate::ui_state::{BoundaryCondition, Integrator, Units};
+
+const MAGIC: &[u8; 8] = b"QECHOSIM";
+const VERSION: u32 = 6;
+
+/// A snapshot of the whole simulation that can be written to and read back from disk.
+///
+/// The file starts with the magic bytes, the format version, the grid size, the physics
+/// parameters and the simulation clock, followed by every cell of the grid. All values are little
+/// endian. Older versions are still read, with the parameters they predate left at their defaults
+/// and the clock at 0.
+#[derive(Debug, Clone)]
+pub struct SimulationState {
+    pub grid: Grid,
+    pub params: StepParams,
+    /// The number of time steps taken when the state was saved.
+    pub step_count: u64,
+    /// The simulation time when the state was saved, which time-dependent potentials are
+    /// evaluated at.
+    pub time: f64,
+}
+
+impl SimulationState {
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let mut data = Vec::with_capacity(72 + self.grid.cells.len() * 16);
+        data.extend_from_slice(MAGIC);
+        data.extend_from_slice(&VERSION.to_le_bytes());
+        data.extend_from_slice(&self.grid.width.to_le_bytes());
+        data.extend_from_slice(&self.grid.height.to_le_bytes());
+        data.extend_from_slice(&self.params.time_step.to_le_bytes());
+        data.extend_from_slice(&(self.params.boundary_condition as u32).to_le_bytes());
+        data.extend_from_slice(&self.params.absorbing_width.to_le_bytes());
+        data.extend_from_slice(&self.params.absorbing_strength.to_le_bytes());
+        data.extend_from_slice(&self.params.cell_spacing.to_le_bytes());
+        data.extend_from_slice(&(self.params.integrator as u32).to_le_bytes());
+        data.extend_from_slice(&self.params.hbar.to_le_bytes());
+        data.extend_from_slice(&self.params.mass.to_le_bytes());
+        data.extend_from_slice(&(self.params.units as u32).to_le_bytes());
+        data.extend_from_slice(&self.step_count.to_le_bytes());
+        data.extend_from_slice(&self.time.to_le_bytes());
+        for cell in &self.grid.cells {
+            for value in cell {
+                data.extend_from_slice(&value.to_le_bytes());
+            }
+        }
+
+        fs::write(path, data)
+    }
+
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let data = fs::read(path)?;
+        let mut reader = Reader { data: &data };
+
+        if reader.take(MAGIC.len())? != MAGIC {
+            return Err(invalid_data("not a Quantum Echoes state file"));
+        }
+        let version = reader.u32()?;
+        if version == 0 || version > VERSION {
+            return Err(invalid_data(&format!(
+                "unsupported state file version {}",
+                version
+            )));
+        }
+
+        let width = reader.u32()?;
+        let height = reader.u32()?;
+        if width == 0 || height == 0 {
+            return Err(invalid_data("the grid has no cells"));
+        }
+        let time_step = reader.f32()?;
+        let boundary_condition = BoundaryCondition::from_index(reader.u32()?)
+            .ok_or_else(|| invalid_data("unknown boundary condition"))?;
+        let mut params = StepParams {
+            time_step,
+            boundary_condition,
+            ..Default::default()
+        };
+        if version >= 2 {
+            params.absorbing_width = reader.f32()?;
+            params.absorbing_strength = reader.f32()?;
+        }
+        if version >= 3 {
+            params.cell_spacing = reader.f32()?;
+        }
+        if version >= 4 {
+            params.integrator = Integrator::from_index(reader.u32()?)
+                .ok_or_else(|| invalid_data("unknown integrator"))?;
+        }
+        if version >= 5 {
+            params.hbar = reader.f32()?;
+            params.mass = reader.f32()?;
+            params.units =
+                Units::from_index(reader.u32()?).ok_or_else(|| invalid_data("unknown units"))?;
+        }
+        let (mut step_count, mut time) = (0, 0.0);
+        if version >= 6 {
+            step_count = reader.u64()?;
+            time = reader.f64()?;
+        }
+
+        if reader.data.len() as u64 != width as u64 * height as u64 * 16 {
+            return Err(invalid_data("grid size does not match the file length"));
+        }
+
+        let mut grid = Grid::new(width, height);
+        for cell in grid.cells.iter_mut() {
+            for value in cell.iter_mut() {
+                *value = reader.f32()?;
+            }
+        }
+
+        Ok(SimulationState {
+            grid,
+            params,
+            step_count,
+            time,
+        })
+    }
+}
+
+fn invalid_data(message: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, message)
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> io::Result<&'a [u8]> {
+        if self.data.len() < len {
+            return Err(Error::new(
+                ErrorKind::UnexpectedEof,
+                "state file is truncated",
+            ));
+        }
+        let (head, tail) = self.data.split_at(len);
+        self.data = tail;
+        Ok(head)
+    }
+
+    fn u32(&mut self) -> io::Result<u32> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn f32(&mut self) -> io::Result<f32> {
+        Ok(f32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> io::Result<u64> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    fn f64(&mut self) -> io::Result<f64> {
+        Ok(f64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let mut grid = Grid::new(3, 2);
+        grid.set(2, 1, [0.5, -0.25, 4.0, -0.125]);
+        let state = SimulationState {
+            grid,
+            params: StepParams {
+                time_step: 0.05,
+                boundary_condition: BoundaryCondition::Periodic,
+                integrator: Integrator::CrankNicolson,
+                hbar: 0.6582,
+                units: Units::Nanometre,
+                ..Default::default()
+            },
+            step_count: 1234,
+            time: 61.7,
+        };
+
+        let path = std::env::temp_dir().join(format!("round_trip_{}.qstate", std::process::id()));
+        state.save(&path).unwrap();
+        let loaded = SimulationState::load(&path);
+        fs::remove_file(&path).unwrap();
+        let loaded = loaded.unwrap();
+
+        assert_eq!(loaded.grid, state.grid);
+        assert_eq!(loaded.params.time_step, 0.05);
+        assert_eq!(
+            loaded.params.boundary_condition,
+            BoundaryCondition::Periodic
+        );
+        assert_eq!(loaded.params.integrator, Integrator::CrankNicolson);
+        assert_eq!(loaded.params.hbar, 0.6582);
+        assert_eq!(loaded.params.units, Units::Nanometre);
+        assert_eq!(loaded.step_count, 1234);
+        assert_eq!(loaded.time, 61.7);
+    }
+}
@@ -7,10 +7,29 @@ use egui_winit_vulkano::egui::{Color32, Frame};
 use winit::event::WindowEvent;
 use winit::event_loop::ActiveEventLoop;
 
+use std::collections::VecDeque;
 use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
 
 use crate::app::VulkanManager;
-use crate::simulator::Simulator;
+use crate::cpu_solver::CpuSolver;
+use crate::detector::{self, Detector, DetectorSample, MAX_DETECTORS, MAX_VERTICES, Reading};
+use crate::drive::{DrivenPotential, DrivenSolver};
+use crate::eigenstates::{self, EigenstateSearch};
+use crate::expression::{Expression, PotentialExpression};
+use crate::grid::Grid;
+use crate::image_import::Image;
+use crate::measurement;
+use crate::momentum;
+use crate::observables::Observables;
+use crate::quad_renderer::QuadRenderer;
+use crate::random::Random;
+use crate::scene::Scene;
+use crate::screen::DetectionScreen;
+use crate::simulator::{GpuSolver, Simulator};
+use crate::solver::{self, StepParams};
+use crate::state_file::SimulationState;
 
 #[derive(PartialEq, Debug, Copy, Clone)]
 pub enum SimulationLayer {
@@ -19,6 +38,14 @@ pub enum SimulationLayer {
     Probability,
     Potential,
     WaveFunction,
+    /// The probability current j = (ħ/m)·Im(Ψ*∇Ψ), computed in `frag.glsl`.
+    Current,
+    /// |φ(k)|², the probability density in momentum space, see [`momentum::momentum_grid`].
+    Momentum,
+    /// Only a brush layer, which draws detector regions instead of painting the grid.
+    Detector,
+    /// Only a brush layer, which draws the line of the detection screen.
+    Screen,
 }
 
 impl fmt::Display for SimulationLayer {
@@ -29,6 +56,74 @@ impl fmt::Display for SimulationLayer {
             SimulationLayer::Probability => write!(f, "Probability"),
             SimulationLayer::Potential => write!(f, "Potential"),
             SimulationLayer::WaveFunction => write!(f, "Wave Function"),
+            SimulationLayer::Current => write!(f, "Current"),
+            SimulationLayer::Momentum => write!(f, "Momentum"),
+            SimulationLayer::Detector => write!(f, "Detector"),
+            SimulationLayer::Screen => write!(f, "Screen"),
+        }
+    }
+}
+
+/// How the brush combines with the layer it draws on.
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub enum BrushMode {
+    /// Draws the brush value over the layer. On the potential the layer is pulled towards the
+    /// value, and on the wave function the packet replaces the wave function where it is larger.
+    Set = 0,
+    Add,
+    Subtract,
+    /// Fades the layer out to 0 under the brush.
+    Erase,
+    /// Measures the particle with a click instead of drawing, see [`Measurement`].
+    Measure,
+}
+
+impl fmt::Display for BrushMode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BrushMode::Set => write!(f, "Set"),
+            BrushMode::Add => write!(f, "Add"),
+            BrushMode::Subtract => write!(f, "Subtract"),
+            BrushMode::Erase => write!(f, "Erase"),
+            BrushMode::Measure => write!(f, "Measure"),
+        }
+    }
+}
+
+/// What a click with the Measure brush mode measures, collapsing the wave function onto the
+/// outcome.
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub enum Measurement {
+    /// Where the particle is, anywhere on the grid, see [`measurement::measure_position`].
+    Position,
+    /// Whether the particle is in the detector region that was clicked, see
+    /// [`measurement::measure_region`].
+    Region,
+}
+
+impl fmt::Display for Measurement {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Measurement::Position => write!(f, "Position"),
+            Measurement::Region => write!(f, "Detector Region"),
+        }
+    }
+}
+
+/// The shape that the Detector brush layer draws.
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub enum DetectorShape {
+    /// Dragged out from one corner to the opposite one.
+    Rectangle,
+    /// Clicked out one corner at a time.
+    Polygon,
+}
+
+impl fmt::Display for DetectorShape {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DetectorShape::Rectangle => write!(f, "Rectangle"),
+            DetectorShape::Polygon => write!(f, "Polygon"),
         }
     }
 }
@@ -38,6 +133,402 @@ pub enum BoundaryCondition {
     Dirichlet = 0,
     Neumann,
     Periodic,
+    Absorbing,
+}
+
+impl BoundaryCondition {
+    pub fn from_index(index: u32) -> Option<Self> {
+        match index {
+            0 => Some(BoundaryCondition::Dirichlet),
+            1 => Some(BoundaryCondition::Neumann),
+            2 => Some(BoundaryCondition::Periodic),
+            3 => Some(BoundaryCondition::Absorbing),
+            _ => None,
+        }
+    }
+}
+
+impl FromStr for BoundaryCondition {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "dirichlet" => Ok(BoundaryCondition::Dirichlet),
+            "neumann" => Ok(BoundaryCondition::Neumann),
+            "periodic" => Ok(BoundaryCondition::Periodic),
+            "absorbing" => Ok(BoundaryCondition::Absorbing),
+            _ => Err(format!("unknown boundary condition {}", s)),
+        }
+    }
+}
+
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub enum Integrator {
+    Leapfrog = 0,
+    CrankNicolson,
+    SplitStep,
+}
+
+impl Integrator {
+    pub fn from_index(index: u32) -> Option<Self> {
+        match index {
+            0 => Some(Integrator::Leapfrog),
+            1 => Some(Integrator::CrankNicolson),
+            2 => Some(Integrator::SplitStep),
+            _ => None,
+        }
+    }
+
+    /// Whether the integrator can be used with `boundary_condition`. The split-step Fourier
+    /// integrator only works on periodic domains.
+    pub fn supports(&self, boundary_condition: BoundaryCondition) -> bool {
+        *self != Integrator::SplitStep || boundary_condition == BoundaryCondition::Periodic
+    }
+}
+
+impl fmt::Display for Integrator {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Integrator::Leapfrog => write!(f, "Leapfrog"),
+            Integrator::CrankNicolson => write!(f, "Crank–Nicolson"),
+            Integrator::SplitStep => write!(f, "Split-Step Fourier"),
+        }
+    }
+}
+
+impl FromStr for Integrator {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "leapfrog" => Ok(Integrator::Leapfrog),
+            "crank-nicolson" | "crank_nicolson" => Ok(Integrator::CrankNicolson),
+            "split-step" | "split_step" => Ok(Integrator::SplitStep),
+            _ => Err(format!("unknown integrator {}", s)),
+        }
+    }
+}
+
+/// The physical units that the parameters and readouts are given in. Only the labels and the
+/// values picked when switching between them depend on this, the equations are the same.
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub enum Units {
+    /// Dimensionless units, in which ħ and the mass are usually 1.
+    Natural = 0,
+    /// Nanometres, femtoseconds and electronvolts, for electrons in nanostructures.
+    Nanometre,
+}
+
+impl Units {
+    pub fn from_index(index: u32) -> Option<Self> {
+        match index {
+            0 => Some(Units::Natural),
+            1 => Some(Units::Nanometre),
+            _ => None,
+        }
+    }
+
+    /// ħ in these units.
+    pub fn hbar(&self) -> f32 {
+        match self {
+            Units::Natural => 1.0,
+            Units::Nanometre => 0.658_211_95,
+        }
+    }
+
+    /// The mass of an electron in these units.
+    pub fn electron_mass(&self) -> f32 {
+        match self {
+            Units::Natural => 1.0,
+            Units::Nanometre => 5.685_63,
+        }
+    }
+
+    pub fn length(&self) -> &'static str {
+        match self {
+            Units::Natural => "",
+            Units::Nanometre => "nm",
+        }
+    }
+
+    pub fn time(&self) -> &'static str {
+        match self {
+            Units::Natural => "",
+            Units::Nanometre => "fs",
+        }
+    }
+
+    pub fn energy(&self) -> &'static str {
+        match self {
+            Units::Natural => "",
+            Units::Nanometre => "eV",
+        }
+    }
+
+    pub fn mass(&self) -> &'static str {
+        match self {
+            Units::Natural => "",
+            Units::Nanometre => "eV·fs²/nm²",
+        }
+    }
+
+    pub fn action(&self) -> &'static str {
+        match self {
+            Units::Natural => "",
+            Units::Nanometre => "eV·fs",
+        }
+    }
+
+    pub fn speed(&self) -> &'static str {
+        match self {
+            Units::Natural => "",
+            Units::Nanometre => "nm/fs",
+        }
+    }
+
+    pub fn momentum(&self) -> &'static str {
+        match self {
+            Units::Natural => "",
+            Units::Nanometre => "eV·fs/nm",
+        }
+    }
+
+    pub fn wave_number(&self) -> &'static str {
+        match self {
+            Units::Natural => "",
+            Units::Nanometre => "1/nm",
+        }
+    }
+}
+
+impl fmt::Display for Units {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Units::Natural => write!(f, "Natural"),
+            Units::Nanometre => write!(f, "nm, fs, eV"),
+        }
+    }
+}
+
+impl FromStr for Units {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "natural" => Ok(Units::Natural),
+            "nanometre" | "nanometer" | "nm" => Ok(Units::Nanometre),
+            _ => Err(format!("unknown units {}", s)),
+        }
+    }
+}
+
+/// `label` followed by `unit` in brackets, or just `label` if there is no unit.
+fn with_unit(label: &str, unit: &str) -> String {
+    if unit.is_empty() {
+        String::from(label)
+    } else {
+        format!("{} ({})", label, unit)
+    }
+}
+
+/// `value` followed by `unit`, if there is one.
+fn value_with_unit(value: String, unit: &str) -> String {
+    if unit.is_empty() {
+        value
+    } else {
+        format!("{} {}", value, unit)
+    }
+}
+
+/// How many of the most recent measurements the monitor keeps for its plots.
+const MONITOR_HISTORY: usize = 2000;
+
+/// Expressions for the potential editor to start from, in natural units for a grid of about 256
+/// cells.
+const POTENTIAL_PRESETS: &[(&str, &str)] = &[
+    ("Harmonic Oscillator", "k = 0.0005; 0.5*k*(x^2 + y^2)"),
+    ("Coulomb-like Well", "Z = 4; a = 2; -Z/sqrt(r^2 + a^2)"),
+    ("Step", "V0 = 0.5; V0*(x > 0)"),
+    ("Barrier", "V0 = 2; w = 4; V0*(abs(x) < w)"),
+    (
+        "Single Slit",
+        "V0 = 10; d = 2; w = 8; V0*(abs(x) < d)*(abs(y) > w/2)",
+    ),
+    (
+        "Double Slit",
+        "V0 = 10; d = 2; w = 6; s = 24; V0*(abs(x) < d)*(abs(abs(y) - s/2) > w/2)",
+    ),
+    (
+        "Triple Slit",
+        "V0 = 10; d = 2; w = 6; s = 20; V0*(abs(x) < d)*(abs(y) > w/2)*(abs(abs(y) - s) > w/2)",
+    ),
+    (
+        "Shaken Trap",
+        "k = 0.0005; A = 10; w = 0.05; 0.5*k*((x - A*sin(w*t))^2 + y^2)",
+    ),
+];
+
+/// The colours of the detectors in the plots and on the grid, in order.
+const DETECTOR_COLORS: [Color32; MAX_DETECTORS] = [
+    Color32::WHITE,
+    Color32::LIGHT_GREEN,
+    Color32::LIGHT_BLUE,
+    Color32::YELLOW,
+    Color32::LIGHT_RED,
+    Color32::from_rgb(200, 150, 255),
+    Color32::from_rgb(255, 180, 100),
+    Color32::from_rgb(120, 230, 230),
+];
+
+/// A measurement taken at one point in simulation time, which can be plotted.
+trait Timed {
+    fn time(&self) -> f64;
+}
+
+impl Timed for Observables {
+    fn time(&self) -> f64 {
+        self.time
+    }
+}
+
+impl Timed for DetectorSample {
+    fn time(&self) -> f64 {
+        self.time
+    }
+}
+
+/// A line of a [`plot`]: its name in the legend, its colour and the value it follows.
+type Series<'a, T> = (&'a str, Color32, &'a dyn Fn(&T) -> f64);
+
+/// Draws `series` of the measurements in `history` against time as lines, scaled to fit between
+/// the smallest and largest value of any of them, which are written along the left edge.
+fn plot<T: Timed>(ui: &mut egui::Ui, history: &VecDeque<T>, series: &[Series<T>]) {
+    let (response, painter) =
+        ui.allocate_painter(egui::vec2(ui.available_width(), 64.0), egui::Sense::hover());
+    let rect = response.rect;
+    painter.rect_filled(rect, 2.0, Color32::from_gray(24));
+
+    let (Some(first), Some(last)) = (history.front(), history.back()) else {
+        return;
+    };
+    let values = history
+        .iter()
+        .flat_map(|sample| series.iter().map(move |(_, _, value)| value(sample)));
+    let (min, max) = values
+        .filter(|value| value.is_finite())
+        .fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), value| {
+            (min.min(value), max.max(value))
+        });
+    if min > max {
+        return;
+    }
+    // A constant value is drawn along the middle
+    let (low, high) = if max - min > 1e-12 * max.abs().max(1.0) {
+        (min, max)
+    } else {
+        (min - 0.5, max + 0.5)
+    };
+    let duration = (last.time() - first.time()).max(f64::MIN_POSITIVE);
+
+    for (_, color, value) in series {
+        let points = history
+            .iter()
+            .filter(|sample| value(sample).is_finite())
+            .map(|sample| {
+                let x =
+                    rect.left() + ((sample.time() - first.time()) / duration) as f32 * rect.width();
+                let y =
+                    rect.bottom() - ((value(sample) - low) / (high - low)) as f32 * rect.height();
+                Pos2::new(x, y)
+            })
+            .collect::<Vec<_>>();
+        painter.add(egui::Shape::line(points, egui::Stroke::new(1.5, *color)));
+    }
+
+    let font = egui::FontId::monospace(10.0);
+    painter.text(
+        rect.left_top() + egui::vec2(2.0, 1.0),
+        egui::Align2::LEFT_TOP,
+        format!("{:.4e}", max),
+        font.clone(),
+        Color32::GRAY,
+    );
+    painter.text(
+        rect.left_bottom() + egui::vec2(2.0, -1.0),
+        egui::Align2::LEFT_BOTTOM,
+        format!("{:.4e}", min),
+        font.clone(),
+        Color32::GRAY,
+    );
+    let mut legend = rect.right_top() + egui::vec2(-2.0, 1.0);
+    for (name, color, _) in series.iter().rev() {
+        let text = painter.text(legend, egui::Align2::RIGHT_TOP, *name, font.clone(), *color);
+        legend.x = text.left() - 6.0;
+    }
+}
+
+/// One series per detector, in its colour, of the `values` measured by it.
+fn detector_series<'a>(
+    detectors: &'a [Detector],
+    values: &'a [impl Fn(&DetectorSample) -> f64],
+) -> Vec<Series<'a, DetectorSample>> {
+    detectors
+        .iter()
+        .zip(DETECTOR_COLORS)
+        .zip(values)
+        .map(|((detector, color), value)| {
+            (
+                detector.name.as_str(),
+                color,
+                value as &dyn Fn(&DetectorSample) -> f64,
+            )
+        })
+        .collect()
+}
+
+/// The most hits of the detection screen that are drawn on the grid, the most recent ones.
+const MAX_DRAWN_HITS: usize = 5000;
+
+/// How far the dots of the hits are spread out across the screen line, in cells.
+const HIT_SPREAD: f32 = 6.0;
+
+/// Draws `counts` as a bar chart, scaled so that the largest count fills the height.
+fn histogram(ui: &mut egui::Ui, counts: &[u32]) {
+    let (response, painter) =
+        ui.allocate_painter(egui::vec2(ui.available_width(), 64.0), egui::Sense::hover());
+    let rect = response.rect;
+    painter.rect_filled(rect, 2.0, Color32::from_gray(24));
+
+    let max = counts.iter().copied().max().unwrap_or(0);
+    if max == 0 {
+        return;
+    }
+    let width = rect.width() / counts.len() as f32;
+    for (i, &count) in counts.iter().enumerate() {
+        let left = rect.left() + i as f32 * width;
+        let top = rect.bottom() - count as f32 / max as f32 * rect.height();
+        painter.rect_filled(
+            egui::Rect::from_min_max(Pos2::new(left, top), Pos2::new(left + width, rect.bottom())),
+            0.0,
+            Color32::from_rgb(255, 210, 90),
+        );
+    }
+    painter.text(
+        rect.left_top() + egui::vec2(2.0, 1.0),
+        egui::Align2::LEFT_TOP,
+        max.to_string(),
+        egui::FontId::monospace(10.0),
+        Color32::GRAY,
+    );
+}
+
+/// The relative change from `first` to `last` as a signed percentage.
+fn drift(first: f64, last: f64) -> String {
+    if first == 0.0 {
+        String::new()
+    } else {
+        format!(" ({:+.3}%)", (last - first) / first.abs() * 100.0)
+    }
 }
 
 pub struct UIState {
@@ -54,12 +545,81 @@ pub struct UIState {
     pub brush_y: i32,
     pub brush_enabled: i32,
     pub brush_radius: f32,
-    pub brush_value: i32,
+    pub brush_value: f32,
 
     pub visible_layer: SimulationLayer,
+    pub current_arrows: bool,
+    pub current_scale: f32,
     pub brush_layer: SimulationLayer,
-    
+    pub brush_mode: BrushMode,
+    pub measurement: Measurement,
+    /// The random numbers for the outcomes of measurements.
+    pub measurement_random: Random,
+    pub measurement_message: Option<String>,
+
     pub boundary_condition: BoundaryCondition,
+    pub absorbing_width: f32,
+    pub absorbing_strength: f32,
+    pub integrator: Integrator,
+
+    pub grid_width: u32,
+    pub grid_height: u32,
+    pub cell_spacing: f32,
+    pub hbar: f32,
+    pub mass: f32,
+    pub units: Units,
+
+    pub paused: bool,
+    pub advance_steps: u32,
+    pub steps_per_frame: u32,
+
+    pub solver_difference: Option<f32>,
+
+    pub monitor_enabled: bool,
+    /// The most recent measurements, oldest first.
+    pub observables: VecDeque<Observables>,
+
+    pub detector_shape: DetectorShape,
+    /// The cell where the rectangle being dragged out started.
+    pub detector_start: Option<(i32, i32)>,
+    /// The corners of the polygon being clicked out so far.
+    pub detector_vertices: Vec<(f32, f32)>,
+    /// The most recent detector readings, oldest first.
+    pub detections: VecDeque<DetectorSample>,
+    pub detector_path: String,
+    pub detector_message: Option<String>,
+
+    pub screen: Option<DetectionScreen>,
+    /// The cell where the screen line being dragged out started.
+    pub screen_start: Option<(i32, i32)>,
+    pub screen_seed: u64,
+    /// Whether particles are detected on the screen every frame while the simulation runs.
+    pub screen_continuous: bool,
+    /// How many particles are detected per frame in continuous mode.
+    pub screen_rate: u32,
+    /// How many particles are detected at once with "Detect".
+    pub screen_burst: u32,
+    pub screen_bins: usize,
+    pub screen_message: Option<String>,
+
+    pub clamp_time_step: bool,
+    pub stability_guard: bool,
+    pub stability_message: Option<String>,
+
+    pub eigenstate_count: usize,
+    pub eigenstate_iterations: u32,
+    pub eigenstate_search: Option<EigenstateSearch>,
+    pub eigenstate_dir: String,
+
+    pub potential_expression: String,
+
+    pub state_path: String,
+    pub scene_path: String,
+    pub image_path: String,
+    pub image_scale: f32,
+    pub image_invert: bool,
+    pub image_amplitude: f32,
+    pub state_message: Option<String>,
 }
 
 impl UIState {
@@ -95,56 +655,329 @@ impl UIState {
             brush_y: 0,
             brush_enabled: 0,
             brush_radius: 2.5,
-            brush_value: 8,
+            brush_value: 8.0,
 
             brush_layer: SimulationLayer::WaveFunction,
+            brush_mode: BrushMode::Set,
+            measurement: Measurement::Position,
+            measurement_random: Random::new(1),
+            measurement_message: None,
             visible_layer: SimulationLayer::WaveFunction,
-            
+            current_arrows: false,
+            current_scale: 2.0,
+
             boundary_condition: BoundaryCondition::Neumann,
+            absorbing_width: 24.0,
+            absorbing_strength: 0.5,
+            integrator: Integrator::Leapfrog,
+
+            grid_width: 256,
+            grid_height: 256,
+            cell_spacing: 1.0,
+            hbar: 1.0,
+            mass: 1.0,
+            units: Units::Natural,
+
+            paused: false,
+            advance_steps: 100,
+            steps_per_frame: 1,
+
+            solver_difference: None,
+
+            monitor_enabled: true,
+            observables: VecDeque::new(),
+
+            detector_shape: DetectorShape::Rectangle,
+            detector_start: None,
+            detector_vertices: Vec::new(),
+            detections: VecDeque::new(),
+            detector_path: String::from("detectors.csv"),
+            detector_message: None,
+
+            screen: None,
+            screen_start: None,
+            screen_seed: 1,
+            screen_continuous: false,
+            screen_rate: 5,
+            screen_burst: 100,
+            screen_bins: 64,
+            screen_message: None,
+
+            clamp_time_step: true,
+            stability_guard: true,
+            stability_message: None,
+
+            eigenstate_count: 4,
+            eigenstate_iterations: 50,
+            eigenstate_search: None,
+            eigenstate_dir: String::from("eigenstates"),
+
+            potential_expression: String::from(POTENTIAL_PRESETS[0].1),
+
+            state_path: String::from("simulation.qstate"),
+            scene_path: String::from("scenes/double_slit.toml"),
+            image_path: String::from("potential.png"),
+            image_scale: 1.0,
+            image_invert: false,
+            image_amplitude: 0.3,
+            state_message: None,
+        }
+    }
+
+    pub fn step_params(&self) -> StepParams {
+        StepParams {
+            time_step: self.time_step,
+            boundary_condition: self.boundary_condition,
+            absorbing_width: self.absorbing_width,
+            absorbing_strength: self.absorbing_strength,
+            cell_spacing: self.cell_spacing,
+            integrator: self.integrator,
+            hbar: self.hbar,
+            mass: self.mass,
+            units: self.units,
+        }
+    }
+
+    fn set_step_params(&mut self, params: &StepParams) {
+        self.time_step = params.time_step;
+        self.boundary_condition = params.boundary_condition;
+        self.absorbing_width = params.absorbing_width;
+        self.absorbing_strength = params.absorbing_strength;
+        self.cell_spacing = params.cell_spacing;
+        self.integrator = params.integrator;
+        self.hbar = params.hbar;
+        self.mass = params.mass;
+        self.units = params.units;
+    }
+
+    /// Adds a measurement to the history, dropping those from after it if the clock was reset or
+    /// the simulation rolled back since.
+    fn record_observables(&mut self, sample: Observables) {
+        while self.observables.back().is_some_and(|last| last.time >= sample.time) {
+            self.observables.pop_back();
+        }
+        if self.observables.len() == MONITOR_HISTORY {
+            self.observables.pop_front();
+        }
+        self.observables.push_back(sample);
+    }
+
+    /// Draws with the Detector brush layer, for a press or release of the left mouse button over
+    /// the grid. A rectangle is dragged out from the cell where the button was pressed to the one
+    /// where it was released, and each release adds a corner to the polygon.
+    pub fn detector_input(&mut self, pressed: bool, mgr: &VulkanManager, simulator: &mut Simulator) {
+        let inside = (0..simulator.width as i32).contains(&self.brush_x) && (0..simulator.height as i32).contains(&self.brush_y);
+        let cell = (self.brush_x, self.brush_y);
+        match self.detector_shape {
+            DetectorShape::Rectangle if pressed => {
+                self.detector_start = inside.then_some(cell);
+            }
+            DetectorShape::Rectangle => {
+                if let Some(start) = self.detector_start.take() {
+                    let end = (self.brush_x.clamp(0, simulator.width as i32 - 1), self.brush_y.clamp(0, simulator.height as i32 - 1));
+                    let name = format!("D{}", simulator.detectors().len() + 1);
+                    self.add_detector(mgr, simulator, Detector::rectangle(name, start, end));
+                }
+            }
+            DetectorShape::Polygon if !pressed && inside => {
+                self.detector_vertices.push((cell.0 as f32, cell.1 as f32));
+                if self.detector_vertices.len() == MAX_VERTICES {
+                    self.finish_polygon(mgr, simulator);
+                }
+            }
+            DetectorShape::Polygon => {}
+        }
+    }
+
+    /// Turns the corners clicked out so far into a detector, if there are enough of them.
+    fn finish_polygon(&mut self, mgr: &VulkanManager, simulator: &mut Simulator) {
+        let vertices = std::mem::take(&mut self.detector_vertices);
+        if vertices.len() >= 3 {
+            let name = format!("D{}", simulator.detectors().len() + 1);
+            self.add_detector(mgr, simulator, Detector { name, vertices });
+        }
+    }
+
+    /// Adds a detector, which starts the measurements of all of them over.
+    fn add_detector(&mut self, mgr: &VulkanManager, simulator: &mut Simulator, detector: Detector) {
+        if simulator.detectors().len() == MAX_DETECTORS {
+            self.detector_message = Some(format!("There can be at most {} detectors", MAX_DETECTORS));
+            return;
+        }
+        let mut detectors = simulator.detectors().to_vec();
+        detectors.push(detector);
+        simulator.set_detectors(mgr, detectors);
+        self.detections.clear();
+    }
+
+    /// Draws with the Screen brush layer, for a press or release of the left mouse button over the
+    /// grid. The screen is the line from the cell where the button was pressed to the one where
+    /// it was released, and replaces the previous one along with its hits.
+    pub fn screen_input(&mut self, pressed: bool, simulator: &Simulator) {
+        let cell = (self.brush_x.clamp(0, simulator.width as i32 - 1), self.brush_y.clamp(0, simulator.height as i32 - 1));
+        if pressed {
+            let inside = cell == (self.brush_x, self.brush_y);
+            self.screen_start = inside.then_some(cell);
+        } else if let Some(start) = self.screen_start.take().filter(|&start| start != cell) {
+            let point = |(x, y): (i32, i32)| (x as f32, y as f32);
+            self.screen = Some(DetectionScreen::new(point(start), point(cell), self.screen_seed));
+            self.screen_message = None;
+        }
+    }
+
+    /// Measures the particle with a click of the Measure brush mode over the grid, and replaces the
+    /// wave function with the collapsed one.
+    pub fn measure(&mut self, mgr: &VulkanManager, simulator: &mut Simulator) {
+        let (x, y) = (self.brush_x, self.brush_y);
+        if !(0..simulator.width as i32).contains(&x) || !(0..simulator.height as i32).contains(&y) {
+            return;
+        }
+
+        let mut grid = simulator.read_grid(mgr);
+        let outcome = match self.measurement {
+            Measurement::Position => measurement::measure_position(&mut grid, self.brush_radius, &self.step_params(), &mut self.measurement_random)
+                .map(|(x, y)| format!("Found the particle at cell ({}, {})", x, y)),
+            Measurement::Region => {
+                let Some(detector) = simulator.detectors().iter().find(|detector| detector.contains(x as f32, y as f32)) else {
+                    self.measurement_message = Some(String::from("Click inside a detector region to measure whether the particle is in it"));
+                    return;
+                };
+                measurement::measure_region(&mut grid, |x, y| detector.contains(x as f32, y as f32), &mut self.measurement_random)
+                    .map(|outcome| format!("{} the particle in {} (probability {:.4})", if outcome.inside { "Found" } else { "Did not find" }, detector.name, outcome.probability))
+            }
+        };
+        match outcome {
+            Some(message) => {
+                simulator.write_grid(mgr, &grid);
+                self.measurement_message = Some(message);
+            }
+            None => self.measurement_message = Some(String::from("There is no probability left to measure")),
         }
     }
 
     pub fn handle_event(&mut self, event: &WindowEvent) {
-        self.gui.update(&event);
+        self.gui.update(event);
     }
 
     pub fn setup_gui(
         &mut self,
         mgr: &VulkanManager,
-        simulator: &Simulator,
+        simulator: &mut Simulator,
     ) {
+        for sample in simulator.take_observables(mgr) {
+            self.record_observables(sample);
+        }
+        if let Some(screen) = self.screen.as_mut().filter(|_| self.screen_continuous && !self.paused) {
+            screen.detect(&simulator.read_grid(mgr), self.screen_rate);
+        }
+        for sample in simulator.take_detections() {
+            if self.detections.len() == MONITOR_HISTORY {
+                self.detections.pop_front();
+            }
+            self.detections.push_back(sample);
+        }
+        if let Some(rollback) = simulator.take_rollback() {
+            self.paused = true;
+            let time = |time: f64| value_with_unit(format!("{:.3}", time), self.units.time());
+            let mut message = match rollback.restored_time {
+                Some(restored_time) => format!("At t = {}, {}. Rolled back to t = {} and paused.", time(rollback.time), rollback.instability, time(restored_time)),
+                None => format!("At t = {}, {}, before there was a snapshot to roll back to. Paused.", time(rollback.time), rollback.instability),
+            };
+            let stability_limit = self.step_params().stability_limit(simulator.max_potential());
+            if self.integrator == Integrator::Leapfrog && self.time_step > stability_limit {
+                self.time_step = stability_limit;
+                message += " The time step was lowered to the stability limit.";
+            }
+            self.stability_message = Some(message);
+        }
+
         let side_panel = egui::SidePanel::new(egui::panel::Side::Left, "side-panel");
+        let params = self.step_params();
+        let mut loaded_params = None;
+        let mut finish_polygon = false;
+
+        // Maps cell coordinates to the window, through the viewport the grid is drawn in
+        let window_size = mgr.windows.get_primary_window().unwrap().inner_size();
+        let viewport = QuadRenderer::get_viewport(window_size.width as f32, window_size.height as f32, self.gui_width, simulator);
+        let (grid_width, grid_height) = (simulator.width as f32, simulator.height as f32);
+        let to_screen = |(x, y): (f32, f32)| Pos2::new(
+            viewport.offset[0] + (x + 0.5) / grid_width * viewport.extent[0],
+            viewport.offset[1] + (y + 0.5) / grid_height * viewport.extent[1],
+        );
 
         self.gui.immediate_ui(|gui| {
             let ctx = gui.context();
             ctx.set_pixels_per_point(1.0);
-            
+
             // Draw the arrow indicator at the position of the mouse pointer
             egui::CentralPanel::default()
                 .frame(Frame::NONE.fill(Color32::TRANSPARENT))
                 .show(&ctx, |ui| {
                     let painter = ui.painter();
-                    
-                    let radius = self.speed * 20.0;
-                    
+
+                    let radius = self.speed / params.max_speed() * 100.0;
+
                     let center = Pos2::new(self.mouse_x, self.mouse_y);
                     let endpoint = center + egui::vec2(radius * f32::cos(self.theta), radius * -f32::sin(self.theta));
-                    
+
                     let x = 8.0;
                     let theta_offset = f32::atan2(x * std::f32::consts::FRAC_1_SQRT_2, radius - x * std::f32::consts::FRAC_1_SQRT_2);
                     let arrow_radius = f32::hypot(x * std::f32::consts::FRAC_1_SQRT_2, radius - x * std::f32::consts::FRAC_1_SQRT_2);
-                    
+
                     let point_a = center + egui::vec2(arrow_radius * f32::cos(self.theta + theta_offset), arrow_radius * -f32::sin(self.theta + theta_offset));
                     let point_b = center + egui::vec2(arrow_radius * f32::cos(self.theta - theta_offset), arrow_radius * -f32::sin(self.theta - theta_offset));
-                    
+
                     if self.speed > 0.0 {
                         painter.line_segment([center, endpoint], egui::Stroke::new(2.0, egui::Color32::WHITE));
                         painter.line_segment([endpoint, point_a], egui::Stroke::new(2.0, egui::Color32::WHITE));
                         painter.line_segment([endpoint, point_b], egui::Stroke::new(2.0, egui::Color32::WHITE));
                     }
+
+                    // Read off the wave numbers under the pointer on the Momentum layer
+                    let inside = (0..simulator.width as i32).contains(&self.brush_x) && (0..simulator.height as i32).contains(&self.brush_y);
+                    if self.visible_layer == SimulationLayer::Momentum && inside {
+                        let (k_x, k_y) = momentum::wave_numbers(self.brush_x as u32, self.brush_y as u32, simulator.width, simulator.height, params.cell_spacing);
+                        let unit = self.units.wave_number();
+                        let text = format!("kx = {}\nky = {}", value_with_unit(format!("{:.4}", k_x), unit), value_with_unit(format!("{:.4}", k_y), unit));
+                        painter.text(center + egui::vec2(12.0, 12.0), egui::Align2::LEFT_TOP, text, egui::FontId::monospace(12.0), Color32::WHITE);
+                    }
+
+                    // Outline the detectors, and the one being drawn
+                    for (detector, color) in simulator.detectors().iter().zip(DETECTOR_COLORS) {
+                        let points = detector.vertices.iter().copied().map(to_screen).collect::<Vec<_>>();
+                        painter.add(egui::Shape::closed_line(points.clone(), egui::Stroke::new(1.5, color)));
+                        painter.text(points[0] + egui::vec2(2.0, 2.0), egui::Align2::LEFT_TOP, &detector.name, egui::FontId::monospace(12.0), color);
+                    }
+                    let cursor = (self.brush_x as f32, self.brush_y as f32);
+                    let drawing = egui::Stroke::new(1.0, Color32::GRAY);
+                    if let Some((x, y)) = self.detector_start {
+                        let (x, y) = (x as f32, y as f32);
+                        let corners = [(x.min(cursor.0) - 0.5, y.min(cursor.1) - 0.5), (x.max(cursor.0) + 0.5, y.max(cursor.1) + 0.5)];
+                        painter.rect_stroke(egui::Rect::from_two_pos(to_screen(corners[0]), to_screen(corners[1])), 0.0, drawing, egui::StrokeKind::Middle);
+                    }
+                    if !self.detector_vertices.is_empty() {
+                        let mut points = self.detector_vertices.iter().copied().map(to_screen).collect::<Vec<_>>();
+                        points.push(to_screen(cursor));
+                        painter.add(egui::Shape::line(points, drawing));
+                    }
+
+                    // The detection screen, with its hits spread out to one side of the line
+                    if let Some((x, y)) = self.screen_start {
+                        painter.line_segment([to_screen((x as f32, y as f32)), to_screen(cursor)], drawing);
+                    }
+                    if let Some(screen) = &self.screen {
+                        painter.line_segment([to_screen(screen.start), to_screen(screen.end)], egui::Stroke::new(1.5, Color32::from_rgb(255, 210, 90)));
+                        let length = screen.length();
+                        let normal = ((screen.start.1 - screen.end.1) / length, (screen.end.0 - screen.start.0) / length);
+                        for hit in &screen.hits[screen.hits.len().saturating_sub(MAX_DRAWN_HITS)..] {
+                            let (x, y) = screen.point(hit.position);
+                            let offset = hit.spread * HIT_SPREAD;
+                            painter.circle_filled(to_screen((x + offset * normal.0, y + offset * normal.1)), 1.2, Color32::from_rgb(255, 240, 180));
+                        }
+                    }
                 }
             );
-            
+
             side_panel
                 .show_separator_line(true)
                 .exact_width(self.gui_width)
@@ -152,7 +985,7 @@ impl UIState {
                 .show(&ctx, |ui| {
                     ui.vertical_centered(|ui| ui.heading("Quantum Echoes"));
                     ui.separator();
-                    
+
                     egui::ScrollArea::vertical().show(ui, |ui| {
                         ui.heading("Brush");
                         ui.add(
@@ -160,16 +993,81 @@ impl UIState {
                                 .text("Brush Size")
                         ).on_hover_text("The standard deviation (σ) of the Gaussian wave packet.");
                         ui.add(
-                            egui::widgets::Slider::new(&mut self.brush_value, 1..=10)
+                            egui::widgets::Slider::new(&mut self.brush_value, -10.0..=10.0)
                                 .text("Brush Value"),
-                        ).on_hover_text("The amplitude of the Gaussian wave packet.");
+                        ).on_hover_text("The amplitude of the Gaussian wave packet, or the height of the potential as an energy. Negative potentials draw attractive wells.");
+                        egui::ComboBox::from_label("Brush Mode")
+                            .selected_text(format!("{}", self.brush_mode))
+                            .show_ui(ui, |ui| {
+                                for mode in [BrushMode::Set, BrushMode::Add, BrushMode::Subtract, BrushMode::Erase, BrushMode::Measure] {
+                                    ui.selectable_value(&mut self.brush_mode, mode, mode.to_string());
+                                }
+                            })
+                            .response
+                            .on_hover_text("Set draws the brush value over the layer, Add and Subtract add it to or take it from the layer once every frame for as long as the mouse is held, and Erase fades the layer out to 0. Measure measures the particle with every click instead.");
+                        if self.brush_mode == BrushMode::Measure {
+                            egui::ComboBox::from_label("Measurement")
+                                .selected_text(self.measurement.to_string())
+                                .show_ui(ui, |ui| {
+                                    ui.selectable_value(&mut self.measurement, Measurement::Position, "Position");
+                                    ui.selectable_value(&mut self.measurement, Measurement::Region, "Detector Region");
+                                })
+                                .response
+                                .on_hover_text("Position finds the particle somewhere on the grid with a probability given by |Ψ|², and collapses the wave function to a packet at rest with the brush size as σ. Detector Region finds out whether the particle is in the detector that was clicked, and cuts the wave function off inside or outside of it. Either way the wave function is renormalized to the same total probability.");
+                            if let Some(message) = &self.measurement_message {
+                                ui.label(message);
+                            }
+                        }
                         ui.add(
-                            egui::widgets::Slider::new(&mut self.speed, 0.0..=5.0)
-                                .text("Speed")
-                        ).on_hover_text("The speed of the Gaussian wave packet.");
+                            egui::widgets::Slider::new(&mut self.speed, 0.0..=params.max_speed())
+                                .text(with_unit("Speed", self.units.speed()))
+                        ).on_hover_text("The speed of the Gaussian wave packet, which gives it the momentum m·v.");
                         ui.spacing();
                         ui.separator();
-                        
+
+                        ui.heading("Potential Editor");
+                        egui::ComboBox::from_label("Preset")
+                            .selected_text("Choose...")
+                            .show_ui(ui, |ui| {
+                                for (name, expression) in POTENTIAL_PRESETS {
+                                    if ui.selectable_label(false, *name).clicked() {
+                                        self.potential_expression = String::from(*expression);
+                                    }
+                                }
+                            });
+                        ui.add(egui::TextEdit::multiline(&mut self.potential_expression).code_editor().desired_rows(2).desired_width(f32::INFINITY))
+                            .on_hover_text("A formula for the potential in x and y, measured in physical units from the centre of the grid with y pointing down, and r = √(x² + y²). Definitions such as k = 0.01; can come first. Comparisons like abs(x) < w are 1 where they hold and 0 elsewhere, and using the simulation time t makes the potential change over time.");
+                        let parsed = Expression::parse(&self.potential_expression);
+                        match &parsed {
+                            Err(err) => {
+                                ui.colored_label(Color32::LIGHT_RED, err);
+                            }
+                            Ok(expression) if expression.depends_on_time() => {
+                                ui.label("Depends on t, so it is evaluated at every step.");
+                            }
+                            Ok(_) => {}
+                        }
+                        ui.horizontal(|ui| {
+                            let set = ui.add_enabled(parsed.is_ok(), egui::Button::new("Set Potential")).on_hover_text("Replaces the potential layer and any time-dependent potentials with the expression.").clicked();
+                            let add = ui.add_enabled(parsed.is_ok(), egui::Button::new("Add to Potential")).on_hover_text("Adds the expression to the potential that is already there.").clicked();
+                            if let Ok(expression) = parsed && (set || add) {
+                                let potential = PotentialExpression::new(expression, simulator.width, simulator.height, params.cell_spacing);
+                                let mut grid = simulator.read_grid(mgr);
+                                let mut drives = if set { Vec::new() } else { simulator.drives().to_vec() };
+                                if potential.expression.depends_on_time() {
+                                    if set {
+                                        grid.cells.iter_mut().for_each(|cell| cell[2] = 0.0);
+                                    }
+                                    drives.push(DrivenPotential::Expression(potential));
+                                } else {
+                                    potential.draw(&mut grid, add);
+                                }
+                                simulator.write_grid(mgr, &grid);
+                                simulator.set_drives(mgr, drives);
+                            }
+                        });
+                        ui.separator();
+
                         ui.heading("Simulation Domain");
                         egui::ComboBox::from_label("Visible Layer")
                             .selected_text(format!("{}", self.visible_layer))
@@ -194,7 +1092,22 @@ impl UIState {
                                     SimulationLayer::WaveFunction,
                                     "Wave Function",
                                 );
+                                ui.selectable_value(
+                                    &mut self.visible_layer,
+                                    SimulationLayer::Current,
+                                    "Current",
+                                );
+                                ui.selectable_value(
+                                    &mut self.visible_layer,
+                                    SimulationLayer::Momentum,
+                                    "Momentum",
+                                );
                             });
+                        ui.horizontal(|ui| {
+                            ui.checkbox(&mut self.current_arrows, "Current Arrows").on_hover_text("Draws arrows along the probability current over any layer, one for every 8 by 8 cells, which grow with the size of the current.");
+                            ui.add(egui::DragValue::new(&mut self.current_scale).speed(0.1).range(0.01..=10000.0)).on_hover_text("How bright the Current layer is and how long the arrows are for a given current. An arrow fills its block at a current of 1 over this scale.");
+                            ui.label("Current Scale");
+                        });
 
                         egui::ComboBox::from_label("Brush Layer")
                             .selected_text(format!("{}", self.brush_layer))
@@ -209,8 +1122,18 @@ impl UIState {
                                     SimulationLayer::Potential,
                                     "Potential",
                                 );
+                                ui.selectable_value(
+                                    &mut self.brush_layer,
+                                    SimulationLayer::Detector,
+                                    "Detector",
+                                );
+                                ui.selectable_value(
+                                    &mut self.brush_layer,
+                                    SimulationLayer::Screen,
+                                    "Screen",
+                                );
                             });
-                        
+
                         egui::ComboBox::from_label("Boundary Condition")
                             .selected_text(format!("{:?}", self.boundary_condition))
                             .show_ui(ui, |ui| {
@@ -229,19 +1152,490 @@ impl UIState {
                                     BoundaryCondition::Periodic,
                                     "Periodic",
                                 );
+                                ui.selectable_value(
+                                    &mut self.boundary_condition,
+                                    BoundaryCondition::Absorbing,
+                                    "Absorbing",
+                                );
+                            });
+
+                        if self.boundary_condition == BoundaryCondition::Absorbing {
+                            ui.add(
+                                egui::widgets::Slider::new(&mut self.absorbing_width, 1.0..=64.0)
+                                    .text("Absorbing Width"),
+                            ).on_hover_text("How many cells from each edge the absorbing layer reaches into the domain.");
+                            ui.add(
+                                egui::widgets::Slider::new(&mut self.absorbing_strength, 0.0..=2.0)
+                                    .text("Absorbing Strength"),
+                            ).on_hover_text("How strongly the wave function is damped at the very edge. Too high a value reflects part of the wave back into the domain.");
+                        }
+
+                        ui.horizontal(|ui| {
+                            ui.label("Grid Size");
+                            ui.add(egui::DragValue::new(&mut self.grid_width).range(16..=2048));
+                            ui.label("×");
+                            ui.add(egui::DragValue::new(&mut self.grid_height).range(16..=2048));
+                            if ui.button("Apply").on_hover_text("Resizes the simulation grid to the given number of cells. The window size does not affect the grid.").clicked() {
+                                simulator.resize(mgr, self.grid_width, self.grid_height);
+                            }
+                        });
+
+                        ui.add(
+                            egui::widgets::Slider::new(&mut self.cell_spacing, 0.25..=4.0)
+                                .text(with_unit("Cell Spacing", self.units.length())),
+                        ).on_hover_text("The physical distance between neighbouring cells of the grid.");
+
+                        let units = self.units;
+                        egui::ComboBox::from_label("Units")
+                            .selected_text(self.units.to_string())
+                            .show_ui(ui, |ui| {
+                                ui.selectable_value(&mut self.units, Units::Natural, "Natural");
+                                ui.selectable_value(&mut self.units, Units::Nanometre, "nm, fs, eV");
+                            })
+                            .response
+                            .on_hover_text("The units of the parameters and readouts. Switching sets ħ and the mass to their values for an electron, and scales the time step to stay as stable as it was.");
+                        if self.units != units {
+                            let stability = self.time_step * self.hbar / (self.mass * self.cell_spacing.powi(2));
+                            self.hbar = self.units.hbar();
+                            self.mass = self.units.electron_mass();
+                            self.time_step = stability * self.mass * self.cell_spacing.powi(2) / self.hbar;
+                        }
+                        ui.horizontal(|ui| {
+                            ui.add(egui::DragValue::new(&mut self.hbar).speed(0.01).range(0.001..=100.0));
+                            ui.label(with_unit("ħ", self.units.action()));
+                            ui.add(egui::DragValue::new(&mut self.mass).speed(0.01).range(0.001..=100.0));
+                            ui.label(with_unit("Mass", self.units.mass()));
+                        }).response.on_hover_text(format!("The reduced Planck constant and the mass of the particle. An electron has a mass of {} in these units.", self.units.electron_mass()));
+
+                        // The time step range keeps the same stability whatever the units
+                        let time_scale = self.mass * self.cell_spacing.powi(2) / self.hbar;
+                        ui.add(
+                            egui::widgets::Slider::new(&mut self.time_step, 0.0..=0.5 * time_scale)
+                                .text(with_unit("Time Step", self.units.time())),
+                        ).on_hover_text("The time in between each frame to advance the simulation by.\n\nNOTE: Setting this above the stability limit makes the simulation \"explode\" from numerical instability with the Leapfrog integrator. The Crank–Nicolson and split-step integrators are stable at any time step.");
+
+                        // The parameters above may have just changed
+                        let stability_limit = StepParams { cell_spacing: self.cell_spacing, hbar: self.hbar, mass: self.mass, ..params }.stability_limit(simulator.max_potential());
+                        if self.integrator == Integrator::Leapfrog && self.time_step > stability_limit {
+                            if self.clamp_time_step {
+                                self.time_step = stability_limit;
+                            } else {
+                                ui.colored_label(Color32::LIGHT_RED, format!("Above the stability limit of {}", value_with_unit(format!("{:.4}", stability_limit), self.units.time())));
+                            }
+                        }
+                        ui.checkbox(&mut self.clamp_time_step, "Clamp to Stability Limit").on_hover_text(format!("Keeps the time step of the Leapfrog integrator at or below {}, the largest at which it is stable with the current cell spacing, ħ, mass and highest potential on the grid.", value_with_unit(format!("{:.4}", stability_limit), self.units.time())));
+                        ui.checkbox(&mut self.stability_guard, "Roll Back on Instability").on_hover_text("Keeps a copy of the grid every 100 steps while the measurements look healthy. If the probability or energy becomes NaN or infinite, or the total probability grows by more than 1%, the simulation is rolled back to the last copy and paused.");
+                        if let Some(message) = &self.stability_message {
+                            ui.colored_label(Color32::LIGHT_RED, message);
+                        }
+
+                        egui::ComboBox::from_label("Integrator")
+                            .selected_text(self.integrator.to_string())
+                            .show_ui(ui, |ui| {
+                                ui.selectable_value(
+                                    &mut self.integrator,
+                                    Integrator::Leapfrog,
+                                    "Leapfrog",
+                                );
+                                ui.selectable_value(
+                                    &mut self.integrator,
+                                    Integrator::CrankNicolson,
+                                    "Crank–Nicolson",
+                                );
+                                if self.boundary_condition == BoundaryCondition::Periodic {
+                                    ui.selectable_value(
+                                        &mut self.integrator,
+                                        Integrator::SplitStep,
+                                        "Split-Step Fourier",
+                                    );
+                                }
                             });
-                        
+
+                        ui.horizontal(|ui| {
+                            if ui.button(if self.paused { "Play" } else { "Pause" }).clicked() {
+                                self.paused = !self.paused;
+                                if !self.paused {
+                                    self.stability_message = None;
+                                }
+                            }
+                            if ui.button("Step").on_hover_text("Advances the simulation by a single time step.").clicked() {
+                                simulator.step(mgr, &params);
+                            }
+                        });
+                        ui.horizontal(|ui| {
+                            ui.add(egui::DragValue::new(&mut self.advance_steps).range(1..=100000));
+                            if ui.button("Advance Steps").on_hover_text("Advances the simulation by the given number of time steps.").clicked() {
+                                simulator.advance(mgr, &params, self.advance_steps);
+                            }
+                        });
                         ui.add(
-                            egui::widgets::Slider::new(&mut self.time_step, 0.0..=0.5)
-                                .text("Time Step"),
-                        ).on_hover_text("The time in between each frame to advance the simulation by.\n\nNOTE: Setting this too high may cause the simulation to \"explode\" from numerical instability. If this happens, reset the simulation using the button below.");
-                        
+                            egui::widgets::Slider::new(&mut self.steps_per_frame, 1..=64)
+                                .text("Steps per Frame")
+                        ).on_hover_text("The number of time steps taken every frame, submitted to the GPU together.");
+                        ui.label(format!("Simulation Time: {} ({} steps)", value_with_unit(format!("{:.3}", simulator.time()), self.units.time()), simulator.step_count()));
+
                         if ui.button("Reset Simulation Domain").clicked() {
                             simulator.zero_grid(mgr);
+                            simulator.set_drives(mgr, Vec::new());
+                            simulator.reset_clock();
+                        }
+
+                        match self.integrator {
+                            Integrator::Leapfrog => {
+                                if ui.button("Check Against CPU Solver").on_hover_text("Advances the simulation by one step on both the GPU and the CPU reference solver, then compares the results cell by cell.").clicked() {
+                                    let cpu = Box::new(CpuSolver::new(Grid::new(simulator.width, simulator.height)));
+                                    let mut cpu = DrivenSolver::wrap(cpu, simulator.drives(), simulator.time());
+                                    let mut gpu = GpuSolver::new(mgr, simulator);
+                                    self.solver_difference = Some(solver::compare_step(&mut gpu, cpu.as_mut(), &params));
+                                }
+                            }
+                            Integrator::CrankNicolson | Integrator::SplitStep => {
+                                if ui.button("Check Against Leapfrog").on_hover_text("Advances a copy of the simulation by one step with both integrators, then compares the results cell by cell. The simulation itself is left untouched.").clicked() {
+                                    let leapfrog = Box::new(CpuSolver::new(simulator.read_grid(mgr)));
+                                    let mut leapfrog = DrivenSolver::wrap(leapfrog, simulator.drives(), simulator.time());
+                                    let candidate = solver::cpu_solver(self.integrator, Grid::new(simulator.width, simulator.height));
+                                    let mut candidate = DrivenSolver::wrap(candidate, simulator.drives(), simulator.time());
+                                    self.solver_difference = Some(solver::compare_step(leapfrog.as_mut(), candidate.as_mut(), &params));
+                                }
+                            }
+                        }
+                        if let Some(difference) = self.solver_difference {
+                            ui.label(format!("Largest difference: {:e}", difference));
+                        }
+
+                        ui.separator();
+
+                        ui.heading("Monitor");
+                        ui.horizontal(|ui| {
+                            ui.checkbox(&mut self.monitor_enabled, "Measure Every Step").on_hover_text("Sums the probability, position, momentum and energy over the grid after each time step, on the GPU for the leapfrog integrator. Probability and energy should stay constant unless the wave function is absorbed or the potential is changed, so drift in either is an early sign of instability.");
+                            if ui.button("Clear").clicked() {
+                                self.observables.clear();
+                            }
+                        });
+                        if let (Some(first), Some(last)) = (self.observables.front(), self.observables.back()) {
+                            let units = self.units;
+                            egui::Grid::new("observables").num_columns(2).show(ui, |ui| {
+                                ui.label("Total Probability");
+                                ui.label(format!("{:.5}{}", last.total_probability, drift(first.total_probability, last.total_probability)));
+                                ui.end_row();
+                                ui.label("⟨x⟩, ⟨y⟩");
+                                ui.label(value_with_unit(format!("{:.3}, {:.3}", last.mean_x, last.mean_y), units.length()));
+                                ui.end_row();
+                                ui.label("⟨pₓ⟩, ⟨p_y⟩");
+                                ui.label(value_with_unit(format!("{:.4}, {:.4}", last.momentum_x, last.momentum_y), units.momentum()));
+                                ui.end_row();
+                                ui.label("Kinetic Energy");
+                                ui.label(value_with_unit(format!("{:.5}", last.kinetic_energy), units.energy()));
+                                ui.end_row();
+                                ui.label("Potential Energy");
+                                ui.label(value_with_unit(format!("{:.5}", last.potential_energy), units.energy()));
+                                ui.end_row();
+                                ui.label("Total Energy");
+                                ui.label(format!("{}{}", value_with_unit(format!("{:.5}", last.total_energy()), units.energy()), drift(first.total_energy(), last.total_energy())));
+                                ui.end_row();
+                            });
+                            ui.label(format!("{} measurements over {}", self.observables.len(), value_with_unit(format!("{:.3}", last.time - first.time), units.time())));
+
+                            plot(ui, &self.observables, &[("|Ψ|²", Color32::WHITE, &|o| o.total_probability)]);
+                            plot(ui, &self.observables, &[
+                                ("T", Color32::LIGHT_BLUE, &|o| o.kinetic_energy),
+                                ("V", Color32::LIGHT_RED, &|o| o.potential_energy),
+                                ("E", Color32::WHITE, &Observables::total_energy),
+                            ]);
+                            plot(ui, &self.observables, &[("⟨x⟩", Color32::LIGHT_GREEN, &|o| o.mean_x), ("⟨y⟩", Color32::YELLOW, &|o| o.mean_y)]);
+                            plot(ui, &self.observables, &[("⟨pₓ⟩", Color32::LIGHT_GREEN, &|o| o.momentum_x), ("⟨p_y⟩", Color32::YELLOW, &|o| o.momentum_y)]);
+                        }
+
+                        ui.separator();
+
+                        ui.heading("Detectors");
+                        ui.horizontal(|ui| {
+                            egui::ComboBox::from_label("Shape")
+                                .selected_text(self.detector_shape.to_string())
+                                .show_ui(ui, |ui| {
+                                    ui.selectable_value(&mut self.detector_shape, DetectorShape::Rectangle, "Rectangle");
+                                    ui.selectable_value(&mut self.detector_shape, DetectorShape::Polygon, "Polygon");
+                                });
+                            if ui.button("Draw").on_hover_text("Switches the brush layer to Detector. Drag across the grid to draw a rectangle, or click the corners of a polygon one at a time.").clicked() {
+                                self.brush_layer = SimulationLayer::Detector;
+                            }
+                        });
+                        if !self.detector_vertices.is_empty() {
+                            ui.horizontal(|ui| {
+                                ui.label(format!("{} corners", self.detector_vertices.len()));
+                                if ui.add_enabled(self.detector_vertices.len() >= 3, egui::Button::new("Finish Polygon")).clicked() {
+                                    finish_polygon = true;
+                                }
+                                if ui.button("Cancel").clicked() {
+                                    self.detector_vertices.clear();
+                                }
+                            });
+                        }
+                        if !simulator.detectors().is_empty() {
+                            let last = self.detections.back();
+                            let mut removed = None;
+                            egui::Grid::new("detectors").num_columns(5).show(ui, |ui| {
+                                ui.label("");
+                                ui.label("Probability").on_hover_text("Σ|Ψ|² over the cells inside, like the total probability in the monitor.");
+                                ui.label("Flux").on_hover_text(with_unit("The rate at which probability leaves through the boundary, negative while it flows in, per unit of time", self.units.time()));
+                                ui.label("Outflow").on_hover_text("The flux added up over every step since the detectors were last changed or cleared, the net probability that has left. Probability that is absorbed inside does not count.");
+                                ui.label("");
+                                ui.end_row();
+                                for (d, (detector, color)) in simulator.detectors().iter().zip(DETECTOR_COLORS).enumerate() {
+                                    ui.colored_label(color, &detector.name);
+                                    match last.and_then(|sample| sample.readings.get(d)) {
+                                        Some(reading) => {
+                                            ui.label(format!("{:.5}", reading.probability));
+                                            ui.label(format!("{:.3e}", reading.flux));
+                                            ui.label(format!("{:.5}", reading.outflow));
+                                        }
+                                        None => {
+                                            ui.label("-");
+                                            ui.label("-");
+                                            ui.label("-");
+                                        }
+                                    }
+                                    if ui.small_button("Remove").clicked() {
+                                        removed = Some(d);
+                                    }
+                                    ui.end_row();
+                                }
+                            });
+                            if let Some(d) = removed {
+                                let mut detectors = simulator.detectors().to_vec();
+                                detectors.remove(d);
+                                simulator.set_detectors(mgr, detectors);
+                                self.detections.clear();
+                            }
+
+                            let readings = |field: fn(&Reading) -> f64| {
+                                (0..simulator.detectors().len())
+                                    .map(move |d| {
+                                        move |sample: &DetectorSample| {
+                                            sample.readings.get(d).map_or(f64::NAN, field)
+                                        }
+                                    })
+                                    .collect::<Vec<_>>()
+                            };
+                            let probability = readings(|reading| reading.probability);
+                            let outflow = readings(|reading| reading.outflow);
+                            ui.label("Probability inside");
+                            plot(ui, &self.detections, &detector_series(simulator.detectors(), &probability));
+                            ui.label("Outflow");
+                            plot(ui, &self.detections, &detector_series(simulator.detectors(), &outflow));
+
+                            ui.horizontal(|ui| {
+                                ui.label("CSV");
+                                ui.text_edit_singleline(&mut self.detector_path);
+                            });
+                            ui.horizontal(|ui| {
+                                if ui.button("Export CSV").on_hover_text("Writes the time, and the probability, flux and outflow of every detector, for each reading that is still kept.").clicked() {
+                                    self.detector_message = Some(match detector::export_csv(Path::new(&self.detector_path), simulator.detectors(), &self.detections) {
+                                        Ok(()) => format!("Saved {} readings to {}", self.detections.len(), self.detector_path),
+                                        Err(err) => format!("Could not save: {}", err),
+                                    });
+                                }
+                                if ui.button("Clear").on_hover_text("Drops the readings and counts the outflow from 0 again.").clicked() {
+                                    simulator.reset_outflow();
+                                    self.detections.clear();
+                                }
+                                if ui.button("Remove All").clicked() {
+                                    simulator.set_detectors(mgr, Vec::new());
+                                    self.detections.clear();
+                                }
+                            });
+                        }
+                        if let Some(message) = &self.detector_message {
+                            ui.label(message);
+                        }
+
+                        ui.separator();
+
+                        ui.heading("Detection Screen");
+                        ui.horizontal(|ui| {
+                            if ui.button("Draw").on_hover_text("Switches the brush layer to Screen. Drag a line across the grid, for example behind a double slit, to place the screen.").clicked() {
+                                self.brush_layer = SimulationLayer::Screen;
+                            }
+                            ui.add(egui::DragValue::new(&mut self.screen_seed).prefix("Seed: "));
+                        });
+                        if let Some(screen) = &mut self.screen {
+                            ui.horizontal(|ui| {
+                                ui.checkbox(&mut self.screen_continuous, "Continuous").on_hover_text("Detects particles every frame while the simulation runs.");
+                                ui.add(egui::DragValue::new(&mut self.screen_rate).range(1..=1000).suffix(" per frame"));
+                            });
+                            ui.horizontal(|ui| {
+                                if ui.button("Detect").on_hover_text("Detects a number of particles in the current wave function at once.").clicked() {
+                                    self.screen_message = (screen.detect(&simulator.read_grid(mgr), self.screen_burst) == 0).then(|| String::from("There is no probability along the screen"));
+                                }
+                                ui.add(egui::DragValue::new(&mut self.screen_burst).range(1..=100000).suffix(" particles"));
+                            });
+                            ui.add(egui::Slider::new(&mut self.screen_bins, 8..=256).text("Bins"));
+                            ui.horizontal(|ui| {
+                                ui.label(format!("{} hits (seed {})", screen.hits.len(), screen.seed()));
+                                if ui.button("Reset").on_hover_text("Clears the hits and starts the random numbers over from the seed, so the same wave function gives the same hits again.").clicked() {
+                                    screen.reset(self.screen_seed);
+                                }
+                            });
+                            histogram(ui, &screen.histogram(self.screen_bins));
+                            if ui.button("Remove Screen").clicked() {
+                                self.screen = None;
+                            }
+                        }
+                        if let Some(message) = &self.screen_message {
+                            ui.label(message);
+                        }
+                        ui.collapsing("Detection Screen Info", |ui| {
+                            ui.add(egui::widgets::Label::new("Every hit is a separate particle prepared in the current state, so detecting it does not change the wave function. The chance of a hit at each point along the line is proportional to |Ψ|² there, so single random dots build up to the interference pattern over many hits. The same seed and wave function always give the same hits."));
+                        });
+
+                        ui.separator();
+
+                        ui.heading("Eigenstates");
+                        ui.horizontal(|ui| {
+                            ui.add(egui::DragValue::new(&mut self.eigenstate_count).range(1..=32));
+                            if ui.button("Find Eigenstates").on_hover_text("Propagates the wave function in imaginary time until it settles into the lowest energy state of the potential, then starts over from noise for each further state, keeping it orthogonal to the states found before. The wave function on the grid is the first guess.").clicked() {
+                                self.eigenstate_search = Some(EigenstateSearch::new(simulator.read_grid(mgr), self.eigenstate_count));
+                            }
+                        });
+                        ui.add(
+                            egui::widgets::Slider::new(&mut self.eigenstate_iterations, 1..=500)
+                                .text("Iterations per Frame")
+                        ).on_hover_text("The number of imaginary time steps taken every frame while searching.");
+                        if let Some(search) = &mut self.eigenstate_search {
+                            if !search.is_finished() {
+                                ui.label(format!("Searching for state {} of {}: E = {}, residual {:.2e} after {} steps", search.states.len() + 1, search.count, value_with_unit(format!("{:.5}", search.energy()), self.units.energy()), search.residual(), search.iterations()));
+                                ui.horizontal(|ui| {
+                                    if ui.button("Accept").on_hover_text("Keeps the current guess as the next state without waiting for it to converge.").clicked() {
+                                        search.accept();
+                                    }
+                                    if ui.button("Stop").on_hover_text("Stops searching, keeping the states found so far.").clicked() {
+                                        search.count = search.states.len();
+                                    }
+                                });
+                            }
+                            for (i, state) in search.states.iter().enumerate() {
+                                ui.horizontal(|ui| {
+                                    ui.label(format!("ψ{}: E = {}", i, value_with_unit(format!("{:.5}", state.energy), self.units.energy())));
+                                    if ui.button("Show").clicked() {
+                                        simulator.write_grid(mgr, &state.grid);
+                                        simulator.reset_clock();
+                                    }
+                                });
+                            }
+                            if !search.states.is_empty() {
+                                ui.horizontal(|ui| {
+                                    ui.text_edit_singleline(&mut self.eigenstate_dir);
+                                    if ui.button("Export").on_hover_text("Writes every state found as a state file, and their energies as a CSV file, to the directory on the left.").clicked() {
+                                        self.state_message = Some(match eigenstates::export(&search.states, &params, Path::new(&self.eigenstate_dir)) {
+                                            Ok(()) => format!("Exported {} states to {}", search.states.len(), self.eigenstate_dir),
+                                            Err(err) => format!("Could not export: {}", err),
+                                        });
+                                    }
+                                });
+                            }
+                        }
+
+                        ui.separator();
+
+                        ui.heading("Simulation State");
+                        ui.horizontal(|ui| {
+                            ui.label("File");
+                            ui.text_edit_singleline(&mut self.state_path);
+                        });
+                        ui.horizontal(|ui| {
+                            if ui.button("Save State").on_hover_text("Writes the wave function, the potential, the physics parameters and the simulation clock to the file above.").clicked() {
+                                let state = SimulationState {
+                                    grid: simulator.read_grid(mgr),
+                                    params,
+                                    step_count: simulator.step_count(),
+                                    time: simulator.time(),
+                                };
+                                self.state_message = Some(match state.save(Path::new(&self.state_path)) {
+                                    Ok(()) => format!("Saved to {}", self.state_path),
+                                    Err(err) => format!("Could not save: {}", err),
+                                });
+                            }
+                            if ui.button("Load State").on_hover_text("Replaces the simulation with the state stored in the file above.").clicked() {
+                                self.state_message = Some(match SimulationState::load(Path::new(&self.state_path)) {
+                                    Ok(state) => {
+                                        simulator.write_grid(mgr, &state.grid);
+                                        simulator.set_drives(mgr, Vec::new());
+                                        simulator.set_clock(state.step_count, state.time);
+                                        (self.grid_width, self.grid_height) = (simulator.width, simulator.height);
+                                        loaded_params = Some(state.params);
+                                        format!("Loaded {}", self.state_path)
+                                    }
+                                    Err(err) => format!("Could not load: {}", err),
+                                });
+                            }
+                        });
+                        ui.horizontal(|ui| {
+                            ui.label("Scene");
+                            ui.text_edit_singleline(&mut self.scene_path);
+                        });
+                        if ui.button("Load Scene").on_hover_text("Replaces the simulation with the wave packets and potentials described by the scene file above.").clicked() {
+                            self.state_message = Some(match Scene::load(Path::new(&self.scene_path)) {
+                                Ok(scene) => {
+                                    let scene_params = scene.step_params(params);
+                                    simulator.write_grid(mgr, &scene.build_grid(simulator.width, simulator.height, &scene_params));
+                                    simulator.set_drives(mgr, scene.drives);
+                                    simulator.reset_clock();
+                                    (self.grid_width, self.grid_height) = (simulator.width, simulator.height);
+                                    loaded_params = Some(scene_params);
+                                    format!("Loaded {}", self.scene_path)
+                                }
+                                Err(err) => format!("Could not load scene: {}", err),
+                            });
+                        }
+                        ui.horizontal(|ui| {
+                            ui.label("Image");
+                            ui.text_edit_singleline(&mut self.image_path);
+                        });
+                        ui.horizontal(|ui| {
+                            if ui.button("Import Potential").on_hover_text("Replaces the potential with the brightness of the PNG image above, stretched over the grid, so that white has the potential below and black has none.").clicked() {
+                                self.state_message = Some(match Image::load(Path::new(&self.image_path)) {
+                                    Ok(image) => {
+                                        let mut grid = simulator.read_grid(mgr);
+                                        image.apply_potential(&mut grid, self.image_scale, self.image_invert);
+                                        simulator.write_grid(mgr, &grid);
+                                        format!("Imported the potential from {}", self.image_path)
+                                    }
+                                    Err(err) => format!("Could not import image: {}", err),
+                                });
+                            }
+                            ui.add(egui::DragValue::new(&mut self.image_scale).speed(0.01).range(-100.0..=100.0));
+                            ui.label(self.units.energy());
+                            ui.checkbox(&mut self.image_invert, "Invert").on_hover_text("Makes black the highest potential and white none, for drawings of dark walls on a light background.");
+                        });
+                        ui.horizontal(|ui| {
+                            if ui.button("Import Wave Function").on_hover_text("Replaces the wave function with the PNG image above, stretched over the grid and read like the Wave Function layer: the hue is the phase and the brightness is the probability density, with white at the amplitude below.").clicked() {
+                                self.state_message = Some(match Image::load(Path::new(&self.image_path)) {
+                                    Ok(image) => {
+                                        let mut grid = simulator.read_grid(mgr);
+                                        image.apply_wave_function(&mut grid, self.image_amplitude);
+                                        simulator.write_grid(mgr, &grid);
+                                        format!("Imported the wave function from {}", self.image_path)
+                                    }
+                                    Err(err) => format!("Could not import image: {}", err),
+                                });
+                            }
+                            ui.add(egui::DragValue::new(&mut self.image_amplitude).speed(0.01).range(0.0..=10.0));
+                            ui.label("|Ψ|");
+                        });
+                        if let Some(message) = &self.state_message {
+                            ui.label(message);
+                        }
+                        if !simulator.drives().is_empty() {
+                            ui.horizontal(|ui| {
+                                ui.label(format!("{} time-dependent potentials", simulator.drives().len())).on_hover_text("Potentials from the scene or the editor that oscillate, move or pulse, which are added to the potential layer at every step. They are not stored in state files, and eigenstate searches leave them out.");
+                                if ui.button("Remove").clicked() {
+                                    simulator.set_drives(mgr, Vec::new());
+                                }
+                            });
                         }
-                        
+
                         ui.separator();
-                        
+
                         egui::CollapsingHeader::new("Brush Info").default_open(true).show(ui, |ui| {
                             ui.add(egui::widgets::Label::new("Click on the simulation domain (to the left) to draw a particle as a Gaussian wave packet with an initial velocity."));
                             ui.add(egui::widgets::Label::new("You can use the mouse scroll wheel to change the direction of this velocity (denoted by the arrow beneath the cursor)"));
@@ -259,12 +1653,33 @@ impl UIState {
                                 ui.separator();
                                 ui.add(egui::widgets::Label::new("Wave Function (Ψ) - The quantum wave function; hue denotes phase and brightness is proportional to amplitude"));
                                 ui.separator();
+                                ui.add(egui::widgets::Label::new("Current (j) - The probability current (ħ/m)·Im(Ψ*∇Ψ), showing where probability flows; hue denotes direction and brightness is proportional to magnitude"));
+                                ui.separator();
+                                ui.add(egui::widgets::Label::new("Momentum (|φ(k)|²) - The probability density in momentum space, from the Fourier transform of the wave function, with k = 0 in the centre and scaled to its largest value; hover over it to read off kx and ky"));
+                                ui.separator();
                             });
                             egui::CollapsingHeader::new("Brush Layer").show(ui, |ui| {
                                 ui.separator();
                                 ui.add(egui::widgets::Label::new("Wave Function - Draw directly on the wave function"));
                                 ui.separator();
-                                ui.add(egui::widgets::Label::new("Potential - Draw regions of higher potential energy which can act as barriers for the wave function"));
+                                ui.add(egui::widgets::Label::new("Potential - Draw regions of higher potential energy which can act as barriers for the wave function, or of negative potential energy which act as wells"));
+                                ui.separator();
+                                ui.add(egui::widgets::Label::new("Detector - Draw regions that measure the probability inside them and the flux through their boundary after every step"));
+                                ui.separator();
+                                ui.add(egui::widgets::Label::new("Screen - Drag a line across the grid on which single particles are detected with a probability given by |Ψ|²"));
+                                ui.separator();
+                            });
+                            egui::CollapsingHeader::new("Brush Mode").show(ui, |ui| {
+                                ui.separator();
+                                ui.add(egui::widgets::Label::new("Set - Draw the brush value over the layer"));
+                                ui.separator();
+                                ui.add(egui::widgets::Label::new("Add - Add the brush value to the layer for as long as the mouse is held"));
+                                ui.separator();
+                                ui.add(egui::widgets::Label::new("Subtract - Take the brush value away from the layer for as long as the mouse is held"));
+                                ui.separator();
+                                ui.add(egui::widgets::Label::new("Erase - Fade the layer out to 0 under the brush"));
+                                ui.separator();
+                                ui.add(egui::widgets::Label::new("Measure - Click to measure the position of the particle, or whether it is in a detector region, and collapse the wave function onto the outcome. Measuring often enough that the wave function cannot spread out in between keeps it in place, the quantum Zeno effect"));
                                 ui.separator();
                             });
                             egui::CollapsingHeader::new("Boundary Conditions").show(ui, |ui| {
@@ -276,11 +1691,28 @@ impl UIState {
                                 ui.separator();
                                 ui.add(egui::widgets::Label::new("Periodic - Each edge wraps around to the opposite edge of the domain"));
                                 ui.separator();
+                                ui.add(egui::widgets::Label::new("Absorbing - A layer along the edges gradually damps the wave function, so waves leave the domain instead of reflecting"));
+                                ui.separator();
                             });
                         });
 
                     });
                 });
         });
+
+        if let Some(params) = loaded_params {
+            self.set_step_params(&params);
+        }
+        if finish_polygon {
+            self.finish_polygon(mgr, simulator);
+        }
+        // The guard needs the measurements even when the monitor is off
+        simulator.measure_observables = self.monitor_enabled || self.stability_guard;
+        simulator.stability_guard = self.stability_guard;
+
+        // Leaving the periodic boundary condition takes the split-step integrator with it
+        if !self.integrator.supports(self.boundary_condition) {
+            self.integrator = Integrator::Leapfrog;
+        }
     }
 }
@@ -0,0 +1,99 @@
+use crate::grid::Grid;
+use crate::solver::StepParams;
+
+/// A Gaussian wave packet with an initial momentum, matching the Wave Function brush in
+/// `compute.glsl`.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct WavePacket {
+    pub x: f32,
+    pub y: f32,
+    /// The standard deviation (σ) of the packet, the same as the brush size.
+    pub sigma: f32,
+    pub amplitude: f32,
+    /// The direction of motion in radians, counterclockwise from the positive x axis.
+    pub theta: f32,
+    /// The speed of the particle in physical units, which gives it the momentum `m·v`.
+    pub speed: f32,
+}
+
+impl WavePacket {
+    /// The value of the packet at a cell, as `(Re(Ψ), Im(Ψ))`.
+    pub fn value(&self, x: f32, y: f32, params: &StepParams) -> (f32, f32) {
+        let pi = std::f32::consts::PI;
+        let s = self.sigma;
+        let r_x = (self.x - x) / s;
+        let r_y = (self.y - y) / s;
+
+        let envelope = self.amplitude * f32::exp(-1.0 / (4.0 * s * s) * (r_x * r_x + r_y * r_y))
+            / f32::sqrt(2.0 * pi * s * s);
+
+        // The phase advances by k·dx per cell along the direction of motion, with y pointing up
+        let k = params.wave_number(self.speed);
+        let (offset_x, offset_y) = (x - self.x, self.y - y);
+        let phase = k
+            * params.cell_spacing
+            * (f32::cos(self.theta) * offset_x + f32::sin(self.theta) * offset_y);
+
+        (envelope * f32::cos(phase), envelope * f32::sin(phase))
+    }
+
+    /// Adds the packet to the wave function already in `grid`, with the momentum given by the
+    /// mass and ħ in `params`.
+    ///
+    /// The previous Im(Ψ) is set to the new Im(Ψ) so that the probability density is correct
+    /// before the first step.
+    pub fn apply(&self, grid: &mut Grid, params: &StepParams) {
+        for y in 0..grid.height {
+            for x in 0..grid.width {
+                let (u, v) = self.value(x as f32, y as f32, params);
+                let [old_u, old_v, potential, _] = grid.get(x, y);
+                grid.set(x, y, [old_u + u, old_v + v, potential, old_v + v]);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::observables::Observables;
+
+    #[test]
+    fn momentum_is_mass_times_speed() {
+        let params = StepParams {
+            mass: 2.0,
+            cell_spacing: 0.5,
+            ..Default::default()
+        };
+        let packet = WavePacket {
+            x: 48.0,
+            y: 48.0,
+            sigma: 3.0,
+            amplitude: 1.0,
+            theta: 0.6,
+            speed: 0.3,
+        };
+        let mut grid = Grid::new(96, 96);
+        packet.apply(&mut grid, &params);
+
+        // The monitor measures with central differences, which see sin(k·dx)/dx instead of k
+        let k = params.wave_number(packet.speed) as f64;
+        let dx = params.cell_spacing as f64;
+        let expected = params.mass as f64 * packet.speed as f64 * (k * dx).sin() / (k * dx);
+        let theta = packet.theta as f64;
+
+        let observables = Observables::from_grid(&grid, 0.0, &params);
+        let (p_x, p_y) = (observables.momentum_x, observables.momentum_y);
+        // The grid's y axis points down
+        assert!(
+            (p_x - expected * theta.cos()).abs() < 0.01 * expected,
+            "{}",
+            p_x
+        );
+        assert!(
+            (p_y + expected * theta.sin()).abs() < 0.01 * expected,
+            "{}",
+            p_y
+        );
+    }
+}